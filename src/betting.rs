@@ -0,0 +1,263 @@
+//%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+// How much to bet on a round, going by the true count
+//
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+//#############################################################################
+// A bet ramp, the number of betting units to bet at each true count
+//
+// Each step gives the bet from a true count upwards, until the next step
+// takes over. Below the first step the minimum is bet, and no bet goes
+// outside the minimum and maximum.
+//
+// Wonging is sitting out the rounds when the count is poor. With `wong_in`
+// the player watches from the shuffle and only joins once the true count
+// reaches it. With `wong_out` the player leaves when it drops below, and
+// waits for the count to reach `wong_in` again or, without one, for the next
+// shoe.
+//
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BetRamp {
+    pub min_units: u32,
+    pub max_units: u32,
+    /// (true count, units) from the lowest true count up.
+    pub steps: Vec<(f64, u32)>,
+    pub wong_in: Option<f64>,
+    pub wong_out: Option<f64>,
+}
+
+impl Default for BetRamp {
+    fn default() -> Self {
+        Self::flat()
+    }
+}
+
+impl BetRamp {
+    /// One unit on every round, whatever the count.
+    pub fn flat() -> Self {
+        Self {
+            min_units: 1,
+            max_units: 1,
+            steps: Vec::new(),
+            wong_in: None,
+            wong_out: None,
+        }
+    }
+
+    /// A ramp betting the units of each step from its true count upwards.
+    pub fn new(min_units: u32, max_units: u32, steps: &[(f64, u32)]) -> Self {
+        Self {
+            min_units,
+            max_units,
+            steps: steps.to_vec(),
+            wong_in: None,
+            wong_out: None,
+        }
+    }
+
+    pub fn is_flat(&self) -> bool {
+        self.min_units == self.max_units && self.wong_in.is_none() && self.wong_out.is_none()
+    }
+
+    /// The units to bet at a true count.
+    pub fn units(&self, true_count: f64) -> u32 {
+        self.steps
+            .iter()
+            .rev()
+            .find(|(from, _)| true_count >= *from)
+            .map_or(self.min_units, |(_, units)| *units)
+            .clamp(self.min_units, self.max_units)
+    }
+
+    /// Is the player at the table just after a shuffle?
+    pub fn seated_at_shuffle(&self) -> bool {
+        self.wong_in.is_none()
+    }
+
+    /// Is the player at the table for a round at the true count, given
+    /// whether they played the last round?
+    pub fn seated(&self, was_seated: bool, true_count: f64) -> bool {
+        if was_seated {
+            !self.wong_out.is_some_and(|out| true_count < out)
+        } else {
+            self.wong_in.is_some_and(|wong_in| true_count >= wong_in)
+        }
+    }
+
+    /// Read a ramp from TOML:
+    ///
+    /// ```toml
+    /// min_units = 1
+    /// max_units = 12
+    /// steps = [[2, 2], [3, 4], [4, 8], [5, 12]]
+    /// wong_out = -1
+    /// ```
+    pub fn from_toml(text: &str) -> Result<BetRamp, RampError> {
+        let ramp: BetRamp =
+            toml::from_str(text).map_err(|error| RampError::Parse(error.to_string()))?;
+        ramp.validate()?;
+        Ok(ramp)
+    }
+
+    /// Load a ramp from a TOML file.
+    pub fn load(path: impl AsRef<Path>) -> Result<BetRamp, RampError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|error| RampError::Io(path.display().to_string(), error))?;
+        Self::from_toml(&text).map_err(|error| match error {
+            RampError::Parse(message) => {
+                RampError::Parse(format!("{}: {}", path.display(), message))
+            }
+            error => error,
+        })
+    }
+
+    /// Check that the bets can be made and the steps go up.
+    pub fn validate(&self) -> Result<(), RampError> {
+        let invalid = |reason: String| Err(RampError::Invalid(reason));
+        if self.min_units == 0 {
+            return invalid("the minimum bet must be at least one unit".to_string());
+        }
+        if self.max_units < self.min_units {
+            return invalid(format!(
+                "the maximum bet of {} units is less than the minimum of {}",
+                self.max_units, self.min_units
+            ));
+        }
+        for pair in self.steps.windows(2) {
+            if pair[1].0 <= pair[0].0 {
+                return invalid(format!(
+                    "the step at a true count of {} comes after {}",
+                    pair[1].0, pair[0].0
+                ));
+            }
+        }
+        if let (Some(wong_in), Some(wong_out)) = (self.wong_in, self.wong_out) {
+            if wong_out > wong_in {
+                return invalid(format!(
+                    "wonging out at {} is above wonging in at {}",
+                    wong_out, wong_in
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Shown as the steps, e.g. "1-12 units: 2 at +2, 4 at +3, wong out below -1"
+impl fmt::Display for BetRamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.min_units == self.max_units {
+            write!(f, "flat {} unit", self.min_units)?;
+            if self.min_units != 1 {
+                write!(f, "s")?;
+            }
+        } else {
+            write!(f, "{}-{} units", self.min_units, self.max_units)?;
+            let steps: Vec<String> = self
+                .steps
+                .iter()
+                .map(|(from, units)| format!("{} at {:+}", units, from))
+                .collect();
+            if !steps.is_empty() {
+                write!(f, ": {}", steps.join(", "))?;
+            }
+        }
+        if let Some(wong_in) = self.wong_in {
+            write!(f, ", wong in at {:+}", wong_in)?;
+        }
+        if let Some(wong_out) = self.wong_out {
+            write!(f, ", wong out below {:+}", wong_out)?;
+        }
+        Ok(())
+    }
+}
+
+//#############################################################################
+// Errors reading a bet ramp
+//
+#[derive(Debug)]
+pub enum RampError {
+    /// Reading the file failed.
+    Io(String, io::Error),
+    /// The file isn't valid TOML or has an unknown key or a bad value.
+    Parse(String),
+    /// The bets can't be made as given.
+    Invalid(String),
+}
+
+impl fmt::Display for RampError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RampError::Io(path, error) => write!(f, "{}: {}", path, error),
+            RampError::Parse(message) => write!(f, "{}", message.trim_end()),
+            RampError::Invalid(reason) => write!(f, "invalid bet ramp: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for RampError {}
+
+//@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@
+// Tests
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bets_go_up_with_the_count() {
+        let ramp = BetRamp::new(1, 8, &[(2.0, 2), (3.0, 4), (4.0, 8), (6.0, 16)]);
+        assert_eq!(ramp.units(-3.0), 1);
+        assert_eq!(ramp.units(1.9), 1);
+        assert_eq!(ramp.units(2.0), 2);
+        assert_eq!(ramp.units(3.5), 4);
+        assert_eq!(ramp.units(7.0), 8);
+        assert_eq!(BetRamp::flat().units(7.0), 1);
+    }
+
+    #[test]
+    fn wonging_in_and_out() {
+        let ramp = BetRamp {
+            wong_in: Some(1.0),
+            wong_out: Some(-1.0),
+            ..BetRamp::new(1, 4, &[])
+        };
+        assert!(!ramp.seated_at_shuffle());
+        assert!(!ramp.seated(false, 0.5));
+        assert!(ramp.seated(false, 1.0));
+        assert!(ramp.seated(true, -0.5));
+        assert!(!ramp.seated(true, -1.5));
+
+        // Wonging out without wonging in waits for the next shoe
+        let ramp = BetRamp {
+            wong_out: Some(-1.0),
+            ..BetRamp::flat()
+        };
+        assert!(ramp.seated_at_shuffle());
+        assert!(!ramp.seated(false, 5.0));
+    }
+
+    #[test]
+    fn ramps_from_toml() {
+        let text = "min_units = 1\nmax_units = 12\nsteps = [[2, 2], [3.5, 6]]\nwong_out = -1\n";
+        let ramp = BetRamp::from_toml(text).unwrap();
+        assert_eq!(ramp.steps, vec![(2.0, 2), (3.5, 6)]);
+        assert_eq!(ramp.wong_out, Some(-1.0));
+        assert_eq!(
+            ramp.to_string(),
+            "1-12 units: 2 at +2, 6 at +3.5, wong out below -1"
+        );
+
+        let error = BetRamp::from_toml("min_units = 4\nmax_units = 2\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid bet ramp: the maximum bet of 2 units is less than the minimum of 4"
+        );
+    }
+}
@@ -0,0 +1,239 @@
+//%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+// Playing cards: rank, suit, a single card and a bunch of cards
+//
+use std::fmt;
+use std::slice::Iter;
+
+//#############################################################################
+// Card's rank (numeric value)
+//
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Rank {
+    ACE,
+    TWO,
+    THREE,
+    FOUR,
+    FIVE,
+    SIX,
+    SEVEN,
+    EIGHT,
+    NINE,
+    TEN,
+    JACK,
+    QUEEN,
+    KING,
+}
+
+impl Rank {
+    /// Return the numeric value of the card's rank. Ace is 1 and
+    /// Jack is 11, Queen is 12, and King is 13.
+    pub fn as_number(&self) -> u8 {
+        match self {
+            Rank::ACE => 1,
+            Rank::TWO => 2,
+            Rank::THREE => 3,
+            Rank::FOUR => 4,
+            Rank::FIVE => 5,
+            Rank::SIX => 6,
+            Rank::SEVEN => 7,
+            Rank::EIGHT => 8,
+            Rank::NINE => 9,
+            Rank::TEN => 10,
+            Rank::JACK => 11,
+            Rank::QUEEN => 12,
+            Rank::KING => 13,
+        }
+    }
+
+    /// Return the value the card counts for in blackjack. Ace is 1 (the hand
+    /// decides if it is worth 11) and the ten and picture cards are all 10.
+    pub fn blackjack_value(&self) -> u8 {
+        self.as_number().min(10)
+    }
+
+    /// Return the rank of the card as a string. For display purposes.
+    pub fn as_string(&self) -> String {
+        match self {
+            Rank::ACE => "ACE".to_string(),
+            Rank::TWO => "TWO".to_string(),
+            Rank::THREE => "THREE".to_string(),
+            Rank::FOUR => "FOUR".to_string(),
+            Rank::FIVE => "FIVE".to_string(),
+            Rank::SIX => "SIX".to_string(),
+            Rank::SEVEN => "SEVEN".to_string(),
+            Rank::EIGHT => "EIGHT".to_string(),
+            Rank::NINE => "NINE".to_string(),
+            Rank::TEN => "TEN".to_string(),
+            Rank::JACK => "JACK".to_string(),
+            Rank::QUEEN => "QUEEN".to_string(),
+            Rank::KING => "KING".to_string(),
+        }
+    }
+
+    /// Return the rank of the card as a string. For display purposes.
+    pub fn as_character(&self) -> String {
+        match self {
+            Rank::ACE => "A".to_string(),
+            Rank::TWO => "2".to_string(),
+            Rank::THREE => "3".to_string(),
+            Rank::FOUR => "4".to_string(),
+            Rank::FIVE => "5".to_string(),
+            Rank::SIX => "6".to_string(),
+            Rank::SEVEN => "7".to_string(),
+            Rank::EIGHT => "8".to_string(),
+            Rank::NINE => "9".to_string(),
+            Rank::TEN => "0".to_string(),
+            Rank::JACK => "J".to_string(),
+            Rank::QUEEN => "Q".to_string(),
+            Rank::KING => "K".to_string(),
+        }
+    }
+
+    /// Allow rank to be used in an iterator. Iteration will progress from ACE to KING.
+    /// Note: Trick is to have a static array and iterate through that.
+    pub fn iterator() -> Iter<'static, Rank> {
+        static RANK: [Rank; 13] = [
+            Rank::ACE,
+            Rank::TWO,
+            Rank::THREE,
+            Rank::FOUR,
+            Rank::FIVE,
+            Rank::SIX,
+            Rank::SEVEN,
+            Rank::EIGHT,
+            Rank::NINE,
+            Rank::TEN,
+            Rank::JACK,
+            Rank::QUEEN,
+            Rank::KING,
+        ];
+        RANK.iter()
+    }
+}
+
+//#############################################################################
+// Card's suit
+//
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Suit {
+    HEARTS,
+    DIAMONDS,
+    CLUBS,
+    SPADES,
+}
+
+impl Suit {
+    /// Return the suit of the card as a string. For display purposes.
+    pub fn as_string(&self) -> String {
+        match self {
+            Suit::HEARTS => "HEARTS".to_string(),
+            Suit::DIAMONDS => "DIAMONDS".to_string(),
+            Suit::CLUBS => "CLUBS".to_string(),
+            Suit::SPADES => "SPADES".to_string(),
+        }
+    }
+
+    /// Return the suit of the card as a string. For display purposes.
+    pub fn as_character(&self) -> String {
+        match self {
+            Suit::HEARTS => "♥".to_string(),
+            Suit::DIAMONDS => "♦".to_string(),
+            Suit::CLUBS => "♣".to_string(),
+            Suit::SPADES => "♠".to_string(),
+        }
+    }
+
+    /// Allow suit to be used in an iterator. Iteration will progress from HEARTS to SPADES.
+    /// Note: Trick is to have a static array and iterate through that.
+    pub fn iterator() -> Iter<'static, Suit> {
+        static SUIT: [Suit; 4] = [Suit::HEARTS, Suit::DIAMONDS, Suit::CLUBS, Suit::SPADES];
+        SUIT.iter()
+    }
+}
+
+//#############################################################################
+// A single playing card
+//
+#[derive(Copy, Clone, PartialEq)]
+pub struct Card {
+    pub rank: Rank,
+    pub suit: Suit,
+}
+
+impl Card {
+    pub fn new(rank: Rank, suit: Suit) -> Self {
+        Self { rank, suit }
+    }
+}
+
+impl fmt::Display for Card {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}",
+            self.rank.as_character(),
+            self.suit.as_character()
+        )
+    }
+}
+
+impl fmt::Debug for Card {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} of {}", self.rank.as_string(), self.suit.as_string())
+    }
+}
+
+//#############################################################################
+// A bunch of playing cards
+//
+// A tuple struct to implement manipulation of a vec of Card. Deref to allow
+// iteration, Display for outputting and new() for creation.
+//
+// Note: See https://github.com/apolitical/impl-display-for-vec
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Cards(pub Vec<Card>);
+
+// Allows code like the following to be used:
+//  ```deck.cards.iter().for_each(|card| println!("{}", card));```
+impl std::ops::Deref for Cards {
+    type Target = Vec<Card>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl std::ops::DerefMut for Cards {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+// Allows code like the following to be used:
+//  ```println!("{}", Cards(deck.cards().to_vec()));```
+impl fmt::Display for Cards {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.iter().try_fold((), |_, card| write!(f, "{}, ", card))
+    }
+}
+
+impl Cards {
+    pub fn new() -> Self {
+        Cards(Vec::<Card>::new())
+    }
+}
+
+//@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@
+// Tests
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn card_creation() {
+        let card = Card::new(Rank::ACE, Suit::DIAMONDS);
+        assert_eq!(card.suit.as_string(), "DIAMONDS");
+        assert_eq!(card.rank.as_string(), "ACE");
+    }
+}
@@ -0,0 +1,487 @@
+//%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+// Composition-dependent strategy, worked out from exact expected values
+//
+use crate::card::{Card, Cards, Rank, Suit};
+use crate::chart::{Chart, ChartAction};
+use crate::composition::Composition;
+use crate::ev::{ActionEvs, EvCalculator};
+use crate::game::Action;
+use crate::hand::HandValue;
+use crate::rules::RuleSet;
+use crate::strategy::{BasicStrategy, Strategy};
+use std::collections::HashMap;
+use std::fmt;
+
+// The cards of a hand by blackjack value, as counts of aces to tens
+type HandKey = [u8; 10];
+
+//#############################################################################
+// A strategy that plays each hand by its cards rather than its total
+//
+// The expected value of every action is worked out for every hand the player
+// can hold against every upcard, off the top of a full shoe. Play then picks
+// the best allowed action for the exact cards held, so 10-6 and 4-5-7 against
+// a ten can be played differently. Hands after a split are looked up by
+// their cards too, valued as if dealt straight off, and play the best of the
+// actions they are allowed. That picks the same action, as early surrender
+// only moves the other actions against surrendering, which a split hand can't
+// do. Anything not worked out is left to basic strategy, including every 21
+// such as a split A-10, which it stands on.
+//
+#[derive(Clone, Debug)]
+pub struct CompositionStrategy {
+    rules: RuleSet,
+    shoe: Composition,
+    plays: HashMap<(HandKey, u8), ActionEvs>,
+    basic: BasicStrategy,
+}
+
+impl CompositionStrategy {
+    /// Work out the strategy off the top of a full shoe for the rules.
+    pub fn generate(rules: &RuleSet) -> Self {
+        Self::generate_for(rules, &Composition::decks(rules.decks))
+    }
+
+    /// Work out the strategy for the cards in `shoe`, before any are dealt.
+    pub fn generate_for(rules: &RuleSet, shoe: &Composition) -> Self {
+        let mut calculator = EvCalculator::new(rules);
+        let mut plays = HashMap::new();
+        let available = shoe.by_value();
+        for upcard in 1..=10 {
+            if available[upcard as usize - 1] == 0 {
+                continue;
+            }
+            let mut left = available;
+            left[upcard as usize - 1] -= 1;
+            for key in player_hands(&left) {
+                let hand = cards_of(&key);
+                let dealt: Vec<Card> = hand.iter().copied().chain([card_of(upcard)]).collect();
+                let evs = calculator.action_evs(&hand, card_of(upcard), &shoe.without(&dealt));
+                plays.insert((key, upcard), evs);
+            }
+        }
+        Self {
+            rules: *rules,
+            shoe: *shoe,
+            plays,
+            basic: BasicStrategy::new(rules),
+        }
+    }
+
+    /// The expected values worked out for a hand against an upcard, if any.
+    pub fn evs(&self, hand: &[Card], upcard: Card) -> Option<&ActionEvs> {
+        self.plays
+            .get(&(key_of(hand), upcard.rank.blackjack_value()))
+    }
+
+    /// A total-dependent chart made from the two card hands, playing each
+    /// total the way that is best on average over the hands that make it.
+    pub fn chart(&self) -> Chart {
+        let mut chart = self.basic.chart().clone();
+        let available = self.shoe.by_value();
+        for upcard in 1..=10 {
+            let column = if upcard == 1 { 11 } else { upcard };
+            let mut left = available;
+            left[upcard as usize - 1] = left[upcard as usize - 1].saturating_sub(1);
+
+            // Add up the values of each action for each total, weighted by
+            // how likely the two cards are once the upcard is out
+            let mut totals: HashMap<(bool, u8), [Option<f64>; 5]> = HashMap::new();
+            for first in 1..=10u8 {
+                for second in first..=10u8 {
+                    let mut key = [0; 10];
+                    key[first as usize - 1] += 1;
+                    key[second as usize - 1] += 1;
+                    let Some(evs) = self.plays.get(&(key, upcard)) else {
+                        continue;
+                    };
+                    let hand = cards_of(&key);
+                    let value = HandValue::of(&hand);
+                    if value.blackjack {
+                        continue;
+                    }
+                    let mut weight = left[first as usize - 1] as f64
+                        * (left[second as usize - 1] as f64
+                            - if first == second { 1.0 } else { 0.0 });
+                    if first != second {
+                        weight *= 2.0;
+                    }
+                    let sums = totals.entry((value.soft, value.total)).or_insert([
+                        Some(0.0),
+                        Some(0.0),
+                        Some(0.0),
+                        None,
+                        Some(0.0),
+                    ]);
+                    for (sum, ev) in sums.iter_mut().zip([
+                        Some(evs.hit),
+                        Some(evs.stand),
+                        evs.double,
+                        None,
+                        evs.surrender,
+                    ]) {
+                        *sum = match (*sum, ev) {
+                            (Some(sum), Some(ev)) => Some(sum + weight * ev),
+                            _ => None,
+                        };
+                    }
+
+                    // A pair gets its own row when splitting is best
+                    if evs.split.is_some() {
+                        let pair = Chart::card_value(hand[0].rank);
+                        let action = chart_action(evs);
+                        if action.is_split() {
+                            chart.set_pair(pair, column, action);
+                        } else {
+                            chart.set_pair(pair, column, ChartAction::Hit);
+                        }
+                    }
+                }
+            }
+
+            for ((soft, total), sums) in totals {
+                let evs = ActionEvs {
+                    hit: sums[0].unwrap_or(f64::MIN),
+                    stand: sums[1].unwrap_or(f64::MIN),
+                    double: sums[2],
+                    split: None,
+                    surrender: sums[4],
+                };
+                let action = chart_action(&evs);
+                if soft {
+                    chart.set_soft(total, column, action);
+                } else {
+                    chart.set_hard(total, column, action);
+                }
+            }
+
+            // Pairs that aren't split are played as their total
+            for pair in Chart::PAIR_VALUES {
+                if !chart.pair(pair, column).is_split() {
+                    let action = if pair == 11 {
+                        chart.soft(12, column)
+                    } else {
+                        chart.hard(pair * 2, column)
+                    };
+                    chart.set_pair(pair, column, action);
+                }
+            }
+        }
+        chart
+    }
+
+    /// The hands where the best play differs from basic strategy, for the
+    /// first decision on the hand.
+    pub fn differences(&self) -> Vec<Difference> {
+        let mut differences = Vec::new();
+        for ((key, upcard), evs) in &self.plays {
+            let mut allowed = vec![Action::Hit, Action::Stand];
+            for action in [Action::Double, Action::Split, Action::Surrender] {
+                if evs.get(action).is_some() {
+                    allowed.push(action);
+                }
+            }
+            let hand = cards_of(key);
+            let (best, best_ev) = evs.best_of(&allowed);
+            let basic = self
+                .basic
+                .decide(&hand, card_of(*upcard), &allowed, &self.rules);
+            let basic_ev = evs
+                .get(basic)
+                .expect("basic strategy picks an allowed action");
+            if best != basic && best_ev > basic_ev {
+                differences.push(Difference {
+                    hand: *key,
+                    upcard: *upcard,
+                    basic,
+                    composition: best,
+                    gain: best_ev - basic_ev,
+                });
+            }
+        }
+        differences.sort_by(|a, b| {
+            (
+                a.upcard == 1,
+                a.upcard,
+                a.cards().len(),
+                a.total(),
+                a.cards(),
+            )
+                .cmp(&(
+                    b.upcard == 1,
+                    b.upcard,
+                    b.cards().len(),
+                    b.total(),
+                    b.cards(),
+                ))
+        });
+        differences
+    }
+}
+
+impl Strategy for CompositionStrategy {
+    fn decide(&self, hand: &Cards, upcard: Card, allowed: &[Action], rules: &RuleSet) -> Action {
+        match self.evs(hand, upcard) {
+            Some(evs) => evs.best_of(allowed).0,
+            _ => self.basic.decide(hand, upcard, allowed, rules),
+        }
+    }
+}
+
+//#############################################################################
+// A hand played differently by composition-dependent and basic strategy
+//
+#[derive(Clone, Debug, PartialEq)]
+pub struct Difference {
+    hand: HandKey,
+    pub upcard: u8,
+    pub basic: Action,
+    pub composition: Action,
+    /// How much more the composition-dependent play is worth, per unit bet.
+    pub gain: f64,
+}
+
+impl Difference {
+    /// The blackjack values of the player's cards, highest first with aces
+    /// as 1.
+    pub fn cards(&self) -> Vec<u8> {
+        let mut cards = Vec::new();
+        for value in (1..=10u8).rev() {
+            (0..self.hand[value as usize - 1]).for_each(|_| cards.push(value));
+        }
+        cards
+    }
+
+    fn total(&self) -> u8 {
+        self.cards().iter().sum()
+    }
+}
+
+// Shown as e.g. "10-6 vs 10: basic stand, composition hit (+0.0012)"
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = |value: u8| match value {
+            1 => "A".to_string(),
+            value => value.to_string(),
+        };
+        let cards: Vec<String> = self.cards().into_iter().map(name).collect();
+        write!(
+            f,
+            "{} vs {}: basic {}, composition {} ({:+.4})",
+            cards.join("-"),
+            name(self.upcard),
+            self.basic,
+            self.composition,
+            self.gain
+        )
+    }
+}
+
+/// The chart code for a hand's best action, with the next best of hitting and
+/// standing as the fall back.
+fn chart_action(evs: &ActionEvs) -> ChartAction {
+    let (best, _) = evs.best();
+    let hit_or_stand = if evs.hit > evs.stand {
+        Action::Hit
+    } else {
+        Action::Stand
+    };
+    match (best, hit_or_stand) {
+        (Action::Split, _) => ChartAction::Split,
+        (Action::Double, Action::Hit) => ChartAction::DoubleOrHit,
+        (Action::Double, _) => ChartAction::DoubleOrStand,
+        (Action::Surrender, _) if evs.split > Some(evs.hit.max(evs.stand)) => {
+            ChartAction::SurrenderOrSplit
+        }
+        (Action::Surrender, Action::Hit) => ChartAction::SurrenderOrHit,
+        (Action::Surrender, _) => ChartAction::SurrenderOrStand,
+        (Action::Hit, _) => ChartAction::Hit,
+        (Action::Stand, _) => ChartAction::Stand,
+    }
+}
+
+/// Every hand of two or more cards that needs a decision and can be dealt
+/// from the cards available, not counting naturals.
+fn player_hands(available: &[u32; 10]) -> Vec<HandKey> {
+    fn extend(
+        available: &[u32; 10],
+        from: usize,
+        key: &mut HandKey,
+        hard: u8,
+        hands: &mut Vec<HandKey>,
+    ) {
+        let cards: u8 = key.iter().sum();
+        let soft_total = if key[0] > 0 && hard + 10 <= 21 {
+            hard + 10
+        } else {
+            hard
+        };
+        if cards >= 2 && soft_total < 21 {
+            hands.push(*key);
+        }
+        for index in from..10 {
+            let value = index as u8 + 1;
+            if hard + value > 21 || key[index] as u32 >= available[index] {
+                continue;
+            }
+            key[index] += 1;
+            extend(available, index, key, hard + value, hands);
+            key[index] -= 1;
+        }
+    }
+
+    let mut hands = Vec::new();
+    extend(available, 0, &mut [0; 10], 0, &mut hands);
+    hands
+}
+
+fn key_of(cards: &[Card]) -> HandKey {
+    let mut key = [0; 10];
+    cards
+        .iter()
+        .for_each(|card| key[card.rank.blackjack_value() as usize - 1] += 1);
+    key
+}
+
+fn card_of(value: u8) -> Card {
+    let rank = *Rank::iterator()
+        .nth(value as usize - 1)
+        .expect("value is from 1 to 10");
+    Card::new(rank, Suit::SPADES)
+}
+
+fn cards_of(key: &HandKey) -> Cards {
+    let mut cards = Cards::new();
+    for (index, count) in key.iter().enumerate() {
+        (0..*count).for_each(|_| cards.push(card_of(index as u8 + 1)));
+    }
+    cards
+}
+
+//@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@
+// Tests
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::SurrenderRule;
+
+    #[test]
+    fn single_deck_exceptions() {
+        let rules = RuleSet {
+            decks: 1,
+            ..RuleSet::default()
+        };
+        let strategy = CompositionStrategy::generate(&rules);
+        let differences = strategy.differences();
+        let shown: Vec<String> = differences.iter().map(|d| d.to_string()).collect();
+
+        // The best known exception: 16 of three or more cards stands against
+        // a ten where 10-6 hits
+        let three_card_sixteen = differences
+            .iter()
+            .find(|d| d.cards() == [6, 5, 5] && d.upcard == 10)
+            .unwrap_or_else(|| panic!("{:#?}", shown));
+        assert_eq!(three_card_sixteen.composition, Action::Stand);
+        assert!(differences.iter().all(|d| d.gain > 0.0));
+
+        // The hands play their own way
+        let hand = cards_of(&key_of(&[card_of(6), card_of(5), card_of(5)]));
+        let allowed = [Action::Hit, Action::Stand];
+        assert_eq!(
+            strategy.decide(&hand, card_of(10), &allowed, &rules),
+            Action::Stand
+        );
+        let hand = cards_of(&key_of(&[card_of(10), card_of(6)]));
+        assert_eq!(
+            strategy.decide(&hand, card_of(10), &allowed, &rules),
+            Action::Hit
+        );
+    }
+
+    #[test]
+    fn multi_card_sixteen_stands_against_ten() {
+        // In six decks too 10-6 hits against a ten but many 16s of three or
+        // more cards stand
+        let rules = RuleSet::default();
+        let strategy = CompositionStrategy::generate(&rules);
+        let allowed = [Action::Hit, Action::Stand];
+        let differences = strategy.differences();
+        for hand in [&[7, 5, 4][..], &[8, 4, 4], &[4, 4, 4, 4]] {
+            let cards: Vec<Card> = hand.iter().map(|value| card_of(*value)).collect();
+            let cards = cards_of(&key_of(&cards));
+            assert_eq!(
+                strategy.decide(&cards, card_of(10), &allowed, &rules),
+                Action::Stand,
+                "{:?}",
+                hand
+            );
+            let mut sorted = hand.to_vec();
+            sorted.sort_by(|a, b| b.cmp(a));
+            assert!(differences
+                .iter()
+                .any(|d| d.cards() == sorted && d.upcard == 10 && d.basic == Action::Hit));
+        }
+    }
+
+    #[test]
+    fn hands_after_a_split() {
+        let rules = RuleSet {
+            decks: 1,
+            surrender: SurrenderRule::Early,
+            ..RuleSet::default()
+        };
+        let strategy = CompositionStrategy::generate(&rules);
+        let first = [
+            Action::Hit,
+            Action::Stand,
+            Action::Double,
+            Action::Surrender,
+        ];
+        let after_split = [Action::Hit, Action::Stand, Action::Double];
+
+        // A split ace and a ten is left to basic strategy, which stands
+        let hand = cards_of(&key_of(&[card_of(1), card_of(10)]));
+        assert_eq!(
+            strategy.decide(&hand, card_of(10), &after_split, &rules),
+            Action::Stand
+        );
+
+        // Early surrender is out after a split, and the hand is played on
+        let hand = cards_of(&key_of(&[card_of(10), card_of(6)]));
+        assert_eq!(
+            strategy.decide(&hand, card_of(10), &first, &rules),
+            Action::Surrender
+        );
+        assert_eq!(
+            strategy.decide(&hand, card_of(10), &after_split, &rules),
+            Action::Hit
+        );
+
+        // Doubling after a split only when the rules allow it
+        let hand = cards_of(&key_of(&[card_of(8), card_of(3)]));
+        assert_eq!(
+            strategy.decide(&hand, card_of(6), &after_split, &rules),
+            Action::Double
+        );
+        assert_eq!(
+            strategy.decide(&hand, card_of(6), &[Action::Hit, Action::Stand], &rules),
+            Action::Hit
+        );
+    }
+
+    #[test]
+    fn chart_matches_basic_strategy_mostly() {
+        let rules = RuleSet {
+            decks: 1,
+            ..RuleSet::default()
+        };
+        let chart = CompositionStrategy::generate(&rules).chart();
+        assert_eq!(chart.hard(11, 6), ChartAction::DoubleOrHit);
+        assert_eq!(chart.hard(16, 10), ChartAction::Hit);
+        assert_eq!(chart.hard(17, 10), ChartAction::Stand);
+        assert_eq!(chart.pair(8, 10), ChartAction::Split);
+        assert_eq!(chart.pair(10, 6), ChartAction::Stand);
+        assert_eq!(chart.soft(18, 4), ChartAction::DoubleOrStand);
+    }
+}
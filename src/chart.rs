@@ -0,0 +1,559 @@
+//%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+// Strategy charts and reading and writing them as CSV tables
+//
+use crate::card::{Card, Rank};
+use crate::game::Action;
+use crate::hand::HandValue;
+use crate::rules::RuleSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+//#############################################################################
+// A cell of a strategy chart
+//
+// The codes are the ones used on printed charts. Most say what to do when
+// the preferred action isn't allowed, e.g. "Ds" is double if allowed,
+// otherwise stand.
+//
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ChartAction {
+    Hit,
+    Stand,
+    DoubleOrHit,
+    DoubleOrStand,
+    Split,
+    SplitIfDasOrHit,
+    SurrenderOrHit,
+    SurrenderOrStand,
+    SurrenderOrSplit,
+}
+
+impl ChartAction {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ChartAction::Hit => "H",
+            ChartAction::Stand => "S",
+            ChartAction::DoubleOrHit => "D",
+            ChartAction::DoubleOrStand => "Ds",
+            ChartAction::Split => "P",
+            ChartAction::SplitIfDasOrHit => "Ph",
+            ChartAction::SurrenderOrHit => "Rh",
+            ChartAction::SurrenderOrStand => "Rs",
+            ChartAction::SurrenderOrSplit => "Rp",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code {
+            "H" => Some(ChartAction::Hit),
+            "S" => Some(ChartAction::Stand),
+            "D" => Some(ChartAction::DoubleOrHit),
+            "Ds" => Some(ChartAction::DoubleOrStand),
+            "P" => Some(ChartAction::Split),
+            "Ph" => Some(ChartAction::SplitIfDasOrHit),
+            "Rh" => Some(ChartAction::SurrenderOrHit),
+            "Rs" => Some(ChartAction::SurrenderOrStand),
+            "Rp" => Some(ChartAction::SurrenderOrSplit),
+            _ => None,
+        }
+    }
+
+    /// Is splitting the first choice?
+    pub fn is_split(&self) -> bool {
+        matches!(
+            self,
+            ChartAction::Split | ChartAction::SplitIfDasOrHit | ChartAction::SurrenderOrSplit
+        )
+    }
+
+    /// The action wanted when everything is allowed.
+    pub fn first_choice(&self, rules: &RuleSet) -> Action {
+        use Action::*;
+        self.resolve(&[Hit, Stand, Double, Split, Surrender], rules)
+    }
+
+    /// Turn the cell into one of the allowed actions, falling back to the
+    /// second choice when the first isn't allowed.
+    pub fn resolve(&self, allowed: &[Action], rules: &RuleSet) -> Action {
+        let can = |action| allowed.contains(&action);
+        let choice = match self {
+            ChartAction::Hit => Action::Hit,
+            ChartAction::Stand => Action::Stand,
+            ChartAction::DoubleOrHit if can(Action::Double) => Action::Double,
+            ChartAction::DoubleOrHit => Action::Hit,
+            ChartAction::DoubleOrStand if can(Action::Double) => Action::Double,
+            ChartAction::DoubleOrStand => Action::Stand,
+            ChartAction::Split => Action::Split,
+            ChartAction::SplitIfDasOrHit if rules.double_after_split => Action::Split,
+            ChartAction::SplitIfDasOrHit => Action::Hit,
+            ChartAction::SurrenderOrHit if can(Action::Surrender) => Action::Surrender,
+            ChartAction::SurrenderOrHit => Action::Hit,
+            ChartAction::SurrenderOrStand if can(Action::Surrender) => Action::Surrender,
+            ChartAction::SurrenderOrStand => Action::Stand,
+            ChartAction::SurrenderOrSplit if can(Action::Surrender) => Action::Surrender,
+            ChartAction::SurrenderOrSplit => Action::Split,
+        };
+        // e.g. a split ace that may only stand
+        if can(choice) {
+            choice
+        } else if can(Action::Stand) {
+            Action::Stand
+        } else {
+            allowed[0]
+        }
+    }
+}
+
+impl fmt::Display for ChartAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+//#############################################################################
+// A strategy chart with hard, soft and pair sections
+//
+// Each row is a player total (or pair) and each column a dealer upcard, from
+// 2 to 10 and then ace. A two card pair is played from its pair row, unless
+// the cell says to split and the hand can't be split, when it is played from
+// its total.
+//
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Chart {
+    hard: [[ChartAction; 10]; 18],
+    soft: [[ChartAction; 10]; 10],
+    pairs: [[ChartAction; 10]; 10],
+}
+
+impl Chart {
+    /// Hard totals in the chart. Lower totals are played as the lowest.
+    pub const HARD_TOTALS: std::ops::RangeInclusive<u8> = 4..=21;
+    /// Soft totals in the chart, soft 12 being two aces that can't be split.
+    pub const SOFT_TOTALS: std::ops::RangeInclusive<u8> = 12..=21;
+    /// Pairs in the chart by the value of one card, 2 to 10 and then ace as 11.
+    pub const PAIR_VALUES: std::ops::RangeInclusive<u8> = 2..=11;
+
+    /// A chart that says to hit everything.
+    pub fn new() -> Self {
+        Self {
+            hard: [[ChartAction::Hit; 10]; 18],
+            soft: [[ChartAction::Hit; 10]; 10],
+            pairs: [[ChartAction::Hit; 10]; 10],
+        }
+    }
+
+    /// The chart column for a dealer upcard of the given blackjack value,
+    /// counting an ace as 11.
+    pub fn column(upcard_value: u8) -> usize {
+        assert!(
+            (2..=11).contains(&upcard_value),
+            "upcard value must be 2 to 11"
+        );
+        (upcard_value - 2) as usize
+    }
+
+    /// The blackjack value of a card as used in the chart, with an ace as 11.
+    pub fn card_value(rank: Rank) -> u8 {
+        match rank {
+            Rank::ACE => 11,
+            rank => rank.blackjack_value(),
+        }
+    }
+
+    pub fn hard(&self, total: u8, upcard_value: u8) -> ChartAction {
+        let total = total.clamp(*Self::HARD_TOTALS.start(), *Self::HARD_TOTALS.end());
+        self.hard[(total - Self::HARD_TOTALS.start()) as usize][Self::column(upcard_value)]
+    }
+
+    pub fn soft(&self, total: u8, upcard_value: u8) -> ChartAction {
+        let total = total.clamp(*Self::SOFT_TOTALS.start(), *Self::SOFT_TOTALS.end());
+        self.soft[(total - Self::SOFT_TOTALS.start()) as usize][Self::column(upcard_value)]
+    }
+
+    pub fn pair(&self, card_value: u8, upcard_value: u8) -> ChartAction {
+        self.pairs[(card_value - Self::PAIR_VALUES.start()) as usize][Self::column(upcard_value)]
+    }
+
+    pub fn set_hard(&mut self, total: u8, upcard_value: u8, action: ChartAction) {
+        self.hard[(total - Self::HARD_TOTALS.start()) as usize][Self::column(upcard_value)] =
+            action;
+    }
+
+    pub fn set_soft(&mut self, total: u8, upcard_value: u8, action: ChartAction) {
+        self.soft[(total - Self::SOFT_TOTALS.start()) as usize][Self::column(upcard_value)] =
+            action;
+    }
+
+    pub fn set_pair(&mut self, card_value: u8, upcard_value: u8, action: ChartAction) {
+        self.pairs[(card_value - Self::PAIR_VALUES.start()) as usize][Self::column(upcard_value)] =
+            action;
+    }
+
+    /// The cell for a hand against an upcard, taking the pair section for a
+    /// pair unless it says to split and the hand may not be split.
+    pub fn lookup(&self, hand: &[Card], upcard: Card, can_split: bool) -> ChartAction {
+        let upcard_value = Self::card_value(upcard.rank);
+        let is_pair =
+            hand.len() == 2 && Self::card_value(hand[0].rank) == Self::card_value(hand[1].rank);
+        if is_pair {
+            let pair = self.pair(Self::card_value(hand[0].rank), upcard_value);
+            if can_split || !pair.is_split() {
+                return pair;
+            }
+        }
+        let value = HandValue::of(hand);
+        if value.soft {
+            self.soft(value.total, upcard_value)
+        } else {
+            self.hard(value.total, upcard_value)
+        }
+    }
+}
+
+impl Default for Chart {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//#############################################################################
+// Chart files
+//
+// A chart is written as CSV with a hard, a soft and a pairs section, each
+// starting with a header row naming the section and the dealer upcards 2 to
+// 10 and A. Hard and soft rows start with the player's total and pair rows
+// with the pair, e.g. "8-8" or "A-A". Blank rows between sections are
+// ignored, as are quotes around cells.
+//
+#[derive(Debug)]
+pub enum ChartError {
+    /// Reading or writing the file failed.
+    Io(String, io::Error),
+    /// A row of the file can't be understood.
+    Parse { line: usize, reason: String },
+    /// A row of the chart isn't in the file.
+    MissingRow { section: &'static str, row: String },
+    /// The file named isn't a chart, for the reason given.
+    InFile(String, Box<ChartError>),
+}
+
+impl fmt::Display for ChartError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChartError::Io(path, error) => write!(f, "{}: {}", path, error),
+            ChartError::Parse { line, reason } => write!(f, "line {}: {}", line, reason),
+            ChartError::MissingRow { section, row } => {
+                write!(f, "the {} section has no row for {}", section, row)
+            }
+            ChartError::InFile(path, error) => write!(f, "{}: {}", path, error),
+        }
+    }
+}
+
+impl std::error::Error for ChartError {}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Section {
+    Hard,
+    Soft,
+    Pairs,
+}
+
+impl Section {
+    const ALL: [Section; 3] = [Section::Hard, Section::Soft, Section::Pairs];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Section::Hard => "hard",
+            Section::Soft => "soft",
+            Section::Pairs => "pairs",
+        }
+    }
+
+    fn rows(&self) -> std::ops::RangeInclusive<u8> {
+        match self {
+            Section::Hard => Chart::HARD_TOTALS,
+            Section::Soft => Chart::SOFT_TOTALS,
+            Section::Pairs => Chart::PAIR_VALUES,
+        }
+    }
+
+    fn row_label(&self, row: u8) -> String {
+        match self {
+            Section::Pairs => {
+                let card = Self::upcard_label(row);
+                format!("{}-{}", card, card)
+            }
+            _ => row.to_string(),
+        }
+    }
+
+    fn parse_row_label(&self, label: &str) -> Option<u8> {
+        self.rows().find(|row| self.row_label(*row) == label)
+    }
+
+    fn upcard_label(value: u8) -> String {
+        match value {
+            11 => "A".to_string(),
+            value => value.to_string(),
+        }
+    }
+}
+
+impl Chart {
+    /// Read a chart from CSV. Every row of every section must be present.
+    pub fn from_csv(text: &str) -> Result<Chart, ChartError> {
+        let mut chart = Chart::new();
+        let mut section = None;
+        let mut seen = Vec::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let parse_error = |reason: String| ChartError::Parse {
+                line: index + 1,
+                reason,
+            };
+            let mut cells: Vec<&str> = line
+                .split(',')
+                .map(|cell| cell.trim().trim_matches('"').trim())
+                .collect();
+            if cells.iter().all(|cell| cell.is_empty()) {
+                continue;
+            }
+            // Spreadsheets pad rows out with empty cells to the widest one
+            while cells.last() == Some(&"") {
+                cells.pop();
+            }
+
+            // A header row starts a new section
+            if let Some(header) = Section::ALL
+                .into_iter()
+                .find(|header| cells[0].eq_ignore_ascii_case(header.name()))
+            {
+                let upcards: Vec<String> = (2..=11).map(Section::upcard_label).collect();
+                if cells[1..] != upcards {
+                    return Err(parse_error(format!(
+                        "the {} header should be followed by the upcards {}",
+                        header.name(),
+                        upcards.join(",")
+                    )));
+                }
+                section = Some(header);
+                continue;
+            }
+
+            let section = section.ok_or_else(|| {
+                parse_error(format!("'{}' comes before any section header", cells[0]))
+            })?;
+            let row = section.parse_row_label(cells[0]).ok_or_else(|| {
+                parse_error(format!(
+                    "'{}' is not a row of the {} section",
+                    cells[0],
+                    section.name()
+                ))
+            })?;
+            if seen.contains(&(section, row)) {
+                return Err(parse_error(format!(
+                    "{} appears twice in the {} section",
+                    cells[0],
+                    section.name()
+                )));
+            }
+            seen.push((section, row));
+            if cells.len() != 11 {
+                return Err(parse_error(format!(
+                    "expected 10 cells after {} but found {}",
+                    cells[0],
+                    cells.len() - 1
+                )));
+            }
+            for (upcard, code) in (2..=11).zip(&cells[1..]) {
+                let action = ChartAction::from_code(code).ok_or_else(|| {
+                    parse_error(format!(
+                        "unknown code '{}' for {} against {}",
+                        code,
+                        cells[0],
+                        Section::upcard_label(upcard)
+                    ))
+                })?;
+                match section {
+                    Section::Hard => chart.set_hard(row, upcard, action),
+                    Section::Soft => chart.set_soft(row, upcard, action),
+                    Section::Pairs => chart.set_pair(row, upcard, action),
+                }
+            }
+        }
+
+        for section in Section::ALL {
+            if let Some(row) = section.rows().find(|row| !seen.contains(&(section, *row))) {
+                return Err(ChartError::MissingRow {
+                    section: section.name(),
+                    row: section.row_label(row),
+                });
+            }
+        }
+        Ok(chart)
+    }
+
+    /// Write the chart as CSV in the form read by from_csv().
+    pub fn to_csv(&self) -> String {
+        let mut text = String::new();
+        for section in Section::ALL {
+            if !text.is_empty() {
+                text.push('\n');
+            }
+            text.push_str(section.name());
+            for upcard in 2..=11 {
+                text.push(',');
+                text.push_str(&Section::upcard_label(upcard));
+            }
+            text.push('\n');
+            for row in section.rows() {
+                text.push_str(&section.row_label(row));
+                for upcard in 2..=11 {
+                    let action = match section {
+                        Section::Hard => self.hard(row, upcard),
+                        Section::Soft => self.soft(row, upcard),
+                        Section::Pairs => self.pair(row, upcard),
+                    };
+                    text.push(',');
+                    text.push_str(action.code());
+                }
+                text.push('\n');
+            }
+        }
+        text
+    }
+
+    /// Load a chart from a CSV file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Chart, ChartError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|error| ChartError::Io(path.display().to_string(), error))?;
+        Self::from_csv(&text)
+            .map_err(|error| ChartError::InFile(path.display().to_string(), Box::new(error)))
+    }
+
+    /// Save the chart to a CSV file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ChartError> {
+        let path = path.as_ref();
+        fs::write(path, self.to_csv())
+            .map_err(|error| ChartError::Io(path.display().to_string(), error))
+    }
+}
+
+//@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@
+// Tests
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::DealerRule;
+    use crate::strategy::basic_strategy_chart;
+
+    #[test]
+    fn codes_round_trip() {
+        for code in ["H", "S", "D", "Ds", "P", "Ph", "Rh", "Rs", "Rp"] {
+            assert_eq!(ChartAction::from_code(code).unwrap().code(), code);
+        }
+        assert_eq!(ChartAction::from_code("X"), None);
+        // Would be saved as "D", so isn't taken
+        assert_eq!(ChartAction::from_code("Dh"), None);
+    }
+
+    #[test]
+    fn pair_cells_are_played() {
+        let card = |rank| Card::new(rank, crate::card::Suit::CLUBS);
+        let fives = [card(Rank::FIVE), card(Rank::FIVE)];
+        let nines = [card(Rank::NINE), card(Rank::NINE)];
+        let text = basic_strategy_chart(&RuleSet::default())
+            .to_csv()
+            .replacen("5-5,D,D,D,D,D", "5-5,D,D,D,D,H", 1)
+            .replacen("9-9,P,P,P,P,P", "9-9,P,P,P,P,S", 1);
+        let chart = Chart::from_csv(&text).unwrap();
+        assert_eq!(Chart::from_csv(&chart.to_csv()).unwrap(), chart);
+        assert_eq!(
+            chart.lookup(&fives, card(Rank::SIX), true),
+            ChartAction::Hit
+        );
+        assert_eq!(
+            chart.lookup(&nines, card(Rank::SIX), true),
+            ChartAction::Stand
+        );
+
+        // A split that can't be made is played as the total
+        assert_eq!(
+            chart.lookup(&nines, card(Rank::FIVE), false),
+            ChartAction::Stand
+        );
+        assert_eq!(
+            chart.lookup(&nines, card(Rank::FIVE), true),
+            ChartAction::Split
+        );
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let rules = RuleSet {
+            dealer: DealerRule::HitSoft17,
+            ..RuleSet::vegas_strip()
+        };
+        let chart = basic_strategy_chart(&rules);
+        let text = chart.to_csv();
+        assert!(text.starts_with("hard,2,3,4,5,6,7,8,9,10,A\n4,H,H,"));
+        assert!(text.contains("\n8-8,P,P,P,P,P,P,P,P,P,Rp\n"));
+        assert_eq!(Chart::from_csv(&text).unwrap(), chart);
+    }
+
+    #[test]
+    fn reads_spreadsheet_output() {
+        // Quoted cells and empty rows between sections, as a spreadsheet writes
+        let text = basic_strategy_chart(&RuleSet::default())
+            .to_csv()
+            .replace("\n\n", "\n,,,,,,,,,,\n")
+            .replace("Ds", "\"Ds\"");
+        assert_eq!(
+            Chart::from_csv(&text).unwrap(),
+            basic_strategy_chart(&RuleSet::default())
+        );
+    }
+
+    #[test]
+    fn errors_say_where() {
+        let text = basic_strategy_chart(&RuleSet::default()).to_csv();
+        let bad_code = text.replacen("16,S,S,S,S,S,H", "16,S,S,S,S,S,X", 1);
+        assert_eq!(
+            Chart::from_csv(&bad_code).unwrap_err().to_string(),
+            "line 14: unknown code 'X' for 16 against 7"
+        );
+        let missing = text.replace("A-A,P,P,P,P,P,P,P,P,P,P\n", "");
+        assert_eq!(
+            Chart::from_csv(&missing).unwrap_err().to_string(),
+            "the pairs section has no row for A-A"
+        );
+    }
+
+    #[test]
+    fn reads_rows_padded_with_empty_cells() {
+        let chart = basic_strategy_chart(&RuleSet::default());
+        let text = chart.to_csv().replace('\n', ",,\n");
+        assert_eq!(Chart::from_csv(&text).unwrap(), chart);
+    }
+
+    #[test]
+    fn load_errors_name_the_file() {
+        let path = std::env::temp_dir().join("blackjack-chart-load-error.csv");
+        let text = basic_strategy_chart(&RuleSet::default()).to_csv();
+        fs::write(&path, text.replacen("16,S,S,S,S,S,H", "16,S,S,S,S,S,X", 1)).unwrap();
+        let error = Chart::load(&path).unwrap_err().to_string();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            error,
+            format!(
+                "{}: line 14: unknown code 'X' for 16 against 7",
+                path.display()
+            )
+        );
+    }
+}
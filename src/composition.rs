@@ -0,0 +1,132 @@
+//%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+// How many cards of each rank are left to be dealt
+//
+use crate::card::{Card, Rank};
+use std::fmt;
+
+//#############################################################################
+// The number of cards of each rank in a shoe, ignoring suits and order
+//
+// This is all that matters when working out probabilities. The calculators
+// only care about blackjack values, so by_value() lumps the tens and picture
+// cards together.
+//
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Composition {
+    counts: [u32; 13],
+}
+
+impl Composition {
+    /// No cards at all.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The cards of a full shoe of the given number of decks.
+    pub fn decks(decks: u8) -> Self {
+        Self {
+            counts: [decks as u32 * 4; 13],
+        }
+    }
+
+    /// Count up a bunch of cards.
+    pub fn from_cards(cards: &[Card]) -> Self {
+        let mut composition = Self::new();
+        cards.iter().for_each(|card| composition.add(card.rank));
+        composition
+    }
+
+    pub fn count(&self, rank: Rank) -> u32 {
+        self.counts[Self::index(rank)]
+    }
+
+    /// The number of cards with a blackjack value, 1 for aces to 10 for tens
+    /// and picture cards.
+    pub fn count_value(&self, value: u8) -> u32 {
+        self.by_value()[value as usize - 1]
+    }
+
+    /// The counts by blackjack value, aces first and all ten valued cards last.
+    pub fn by_value(&self) -> [u32; 10] {
+        let mut values = [0; 10];
+        values[..9].copy_from_slice(&self.counts[..9]);
+        values[9] = self.counts[9..].iter().sum();
+        values
+    }
+
+    /// The number of cards left.
+    pub fn total(&self) -> u32 {
+        self.counts.iter().sum()
+    }
+
+    pub fn add(&mut self, rank: Rank) {
+        self.counts[Self::index(rank)] += 1;
+    }
+
+    /// Take out a card of the given rank.
+    ///
+    /// # Panics
+    ///
+    /// If there are none of that rank left.
+    pub fn remove(&mut self, rank: Rank) {
+        let count = &mut self.counts[Self::index(rank)];
+        assert!(*count > 0, "no {} left to remove", rank.as_string());
+        *count -= 1;
+    }
+
+    /// The composition without the given cards, such as those already dealt.
+    ///
+    /// # Panics
+    ///
+    /// If one of the cards isn't there to remove.
+    pub fn without(&self, cards: &[Card]) -> Self {
+        let mut composition = *self;
+        cards.iter().for_each(|card| composition.remove(card.rank));
+        composition
+    }
+
+    fn index(rank: Rank) -> usize {
+        rank.as_number() as usize - 1
+    }
+}
+
+// Shown as the count of each rank, e.g. "A:24 2:24 ... K:24"
+impl fmt::Display for Composition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let counts: Vec<String> = Rank::iterator()
+            .map(|rank| format!("{}:{}", rank.as_character(), self.count(*rank)))
+            .collect();
+        write!(f, "{}", counts.join(" "))
+    }
+}
+
+//@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@
+// Tests
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::Suit;
+    use crate::deck::Deck;
+
+    #[test]
+    fn deck_has_four_of_each() {
+        let composition = Composition::from_cards(Deck::new().cards());
+        assert_eq!(composition, Composition::decks(1));
+        assert_eq!(composition.total(), 52);
+        assert_eq!(composition.count_value(10), 16);
+        assert_eq!(composition.count_value(1), 4);
+    }
+
+    #[test]
+    fn dealt_cards_are_removed() {
+        let dealt = [
+            Card::new(Rank::KING, Suit::HEARTS),
+            Card::new(Rank::ACE, Suit::SPADES),
+        ];
+        let composition = Composition::decks(1).without(&dealt);
+        assert_eq!(composition.total(), 50);
+        assert_eq!(composition.count(Rank::KING), 3);
+        assert_eq!(composition.by_value()[9], 15);
+    }
+}
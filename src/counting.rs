@@ -0,0 +1,382 @@
+//%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+// Card counting systems and keeping a count as cards are dealt
+//
+use crate::card::{Card, Rank};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+//#############################################################################
+// A counting system, the tag added to the count for each rank dealt
+//
+// Tags can be fractions, as in Wong Halves. A balanced system's tags add up
+// to zero over a deck, so the count of a full shoe comes back to zero. An
+// unbalanced one such as KO drifts up as the shoe is dealt.
+//
+#[derive(Clone, Debug, PartialEq)]
+pub struct CountingSystem {
+    name: String,
+    tags: [f64; 13],
+}
+
+impl CountingSystem {
+    /// The names of the built in systems, as accepted by preset().
+    pub const PRESETS: [&'static str; 7] = [
+        "hi-lo",
+        "ko",
+        "hi-opt-1",
+        "hi-opt-2",
+        "omega-2",
+        "zen",
+        "wong-halves",
+    ];
+
+    /// A system with a tag for each rank, from ace to king.
+    pub fn new(name: &str, tags: [f64; 13]) -> Self {
+        Self {
+            name: name.to_string(),
+            tags,
+        }
+    }
+
+    /// A built in system by name.
+    pub fn preset(name: &str) -> Option<CountingSystem> {
+        match name {
+            "hi-lo" => Some(Self::hi_lo()),
+            "ko" => Some(Self::knock_out()),
+            "hi-opt-1" => Some(Self::hi_opt_1()),
+            "hi-opt-2" => Some(Self::hi_opt_2()),
+            "omega-2" => Some(Self::omega_2()),
+            "zen" => Some(Self::zen()),
+            "wong-halves" => Some(Self::wong_halves()),
+            _ => None,
+        }
+    }
+
+    pub fn hi_lo() -> Self {
+        Self::by_value(
+            "Hi-Lo",
+            [-1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.0, 0.0, 0.0, -1.0],
+        )
+    }
+
+    /// Knock-Out, which is unbalanced.
+    pub fn knock_out() -> Self {
+        Self::by_value("KO", [-1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.0, 0.0, -1.0])
+    }
+
+    pub fn hi_opt_1() -> Self {
+        Self::by_value(
+            "Hi-Opt I",
+            [0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 0.0, 0.0, 0.0, -1.0],
+        )
+    }
+
+    pub fn hi_opt_2() -> Self {
+        Self::by_value(
+            "Hi-Opt II",
+            [0.0, 1.0, 1.0, 2.0, 2.0, 1.0, 1.0, 0.0, 0.0, -2.0],
+        )
+    }
+
+    pub fn omega_2() -> Self {
+        Self::by_value(
+            "Omega II",
+            [0.0, 1.0, 1.0, 2.0, 2.0, 2.0, 1.0, 0.0, -1.0, -2.0],
+        )
+    }
+
+    pub fn zen() -> Self {
+        Self::by_value("Zen", [-1.0, 1.0, 1.0, 2.0, 2.0, 2.0, 1.0, 0.0, 0.0, -2.0])
+    }
+
+    pub fn wong_halves() -> Self {
+        Self::by_value(
+            "Wong Halves",
+            [-1.0, 0.5, 1.0, 1.0, 1.5, 1.0, 0.5, 0.0, -0.5, -1.0],
+        )
+    }
+
+    // A system with the same tag for all of the ten valued cards, the tags
+    // given from ace to ten
+    fn by_value(name: &str, tags: [f64; 10]) -> Self {
+        Self::new(name, std::array::from_fn(|index| tags[index.min(9)]))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn tag(&self, rank: Rank) -> f64 {
+        self.tags[rank.as_number() as usize - 1]
+    }
+
+    /// What the tags of a whole deck add up to, zero for a balanced system.
+    pub fn deck_total(&self) -> f64 {
+        self.tags.iter().sum::<f64>() * 4.0
+    }
+
+    pub fn is_balanced(&self) -> bool {
+        self.deck_total() == 0.0
+    }
+
+    /// Start counting a freshly shuffled shoe of the given number of decks.
+    pub fn counter(&self, decks: u8) -> Counter {
+        Counter {
+            tags: self.tags,
+            deck_total: self.deck_total(),
+            decks,
+            running: 0.0,
+            seen: 0,
+        }
+    }
+
+    /// Read a custom system from TOML, a name and a table of tags by rank:
+    ///
+    /// ```toml
+    /// name = "Red Seven"
+    /// [tags]
+    /// A = -1
+    /// 2 = 1
+    /// ```
+    ///
+    /// Ranks are A, 2 to 9 and T, which covers the picture cards too unless
+    /// they have tags of their own as J, Q or K. Ranks left out count zero.
+    pub fn from_toml(text: &str) -> Result<CountingSystem, CountingError> {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct Table {
+            name: String,
+            tags: BTreeMap<String, f64>,
+        }
+
+        let table: Table =
+            toml::from_str(text).map_err(|error| CountingError::Parse(error.to_string()))?;
+        let mut tags = [0.0; 13];
+        for (rank, tag) in &table.tags {
+            let ranks = match rank.as_str() {
+                "A" => 0..1,
+                "T" | "10"
+                    if !["J", "Q", "K"]
+                        .iter()
+                        .any(|face| table.tags.contains_key(*face)) =>
+                {
+                    9..13
+                }
+                "T" | "10" => 9..10,
+                "J" => 10..11,
+                "Q" => 11..12,
+                "K" => 12..13,
+                number => match number.parse::<usize>() {
+                    Ok(number @ 2..=9) => number - 1..number,
+                    _ => {
+                        return Err(CountingError::Parse(format!(
+                            "unknown rank '{}' in tags",
+                            rank
+                        )))
+                    }
+                },
+            };
+            if !tag.is_finite() {
+                return Err(CountingError::Parse(format!(
+                    "the tag for {} isn't a number",
+                    rank
+                )));
+            }
+            ranks.for_each(|index| tags[index] = *tag);
+        }
+        Ok(Self::new(&table.name, tags))
+    }
+
+    /// Load a custom system from a TOML file.
+    pub fn load(path: impl AsRef<Path>) -> Result<CountingSystem, CountingError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|error| CountingError::Io(path.display().to_string(), error))?;
+        Self::from_toml(&text).map_err(|error| match error {
+            CountingError::Parse(message) => {
+                CountingError::Parse(format!("{}: {}", path.display(), message))
+            }
+            error => error,
+        })
+    }
+}
+
+impl Default for CountingSystem {
+    fn default() -> Self {
+        Self::hi_lo()
+    }
+}
+
+// Shown with its tags, e.g. "Hi-Lo (A:-1 2:+1 ... 9:0 T:-1)"
+impl fmt::Display for CountingSystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let labels = [
+            "A", "2", "3", "4", "5", "6", "7", "8", "9", "T", "J", "Q", "K",
+        ];
+        let tag = |index: usize| {
+            let tag = self.tags[index];
+            let tag = if tag == 0.0 {
+                "0".to_string()
+            } else {
+                format!("{:+}", tag)
+            };
+            format!("{}:{}", labels[index], tag)
+        };
+        // The picture cards are only shown when they differ from the ten
+        let ranks = if self.tags[9..].iter().all(|tag| *tag == self.tags[9]) {
+            10
+        } else {
+            13
+        };
+        let tags: Vec<String> = (0..ranks).map(tag).collect();
+        write!(f, "{} ({})", self.name, tags.join(" "))
+    }
+}
+
+//#############################################################################
+// Errors reading a counting system
+//
+#[derive(Debug)]
+pub enum CountingError {
+    /// Reading the file failed.
+    Io(String, io::Error),
+    /// The file isn't valid TOML or has a bad rank or tag.
+    Parse(String),
+}
+
+impl fmt::Display for CountingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CountingError::Io(path, error) => write!(f, "{}: {}", path, error),
+            CountingError::Parse(message) => write!(f, "{}", message.trim_end()),
+        }
+    }
+}
+
+impl std::error::Error for CountingError {}
+
+//#############################################################################
+// The count of a shoe as it is dealt
+//
+// The true count is the running count per deck left to be dealt. For an
+// unbalanced system the drift expected from the cards dealt so far is taken
+// off first, which puts its true count on the same footing as a balanced
+// one.
+//
+#[derive(Clone, Debug)]
+pub struct Counter {
+    tags: [f64; 13],
+    deck_total: f64,
+    decks: u8,
+    running: f64,
+    seen: u32,
+}
+
+impl Counter {
+    /// Count a card that has been seen leaving the shoe.
+    pub fn count(&mut self, card: Card) {
+        self.running += self.tags[card.rank.as_number() as usize - 1];
+        self.seen += 1;
+    }
+
+    /// The count as it would be without a card, such as the dealer's hole
+    /// card that has been dealt but not seen.
+    pub fn without(&self, card: Card) -> Counter {
+        let mut counter = self.clone();
+        counter.running -= self.tags[card.rank.as_number() as usize - 1];
+        counter.seen = counter.seen.saturating_sub(1);
+        counter
+    }
+
+    /// Start again after the shoe is shuffled.
+    pub fn reset(&mut self) {
+        self.running = 0.0;
+        self.seen = 0;
+    }
+
+    pub fn running_count(&self) -> f64 {
+        self.running
+    }
+
+    /// The number of cards counted since the shuffle.
+    pub fn cards_seen(&self) -> u32 {
+        self.seen
+    }
+
+    pub fn decks_remaining(&self) -> f64 {
+        (self.decks as u32 * 52).saturating_sub(self.seen) as f64 / 52.0
+    }
+
+    /// The running count per deck left, or zero once the shoe is empty.
+    pub fn true_count(&self) -> f64 {
+        let decks_remaining = self.decks_remaining();
+        if decks_remaining == 0.0 {
+            return 0.0;
+        }
+        let drift = self.deck_total * self.seen as f64 / 52.0;
+        (self.running - drift) / decks_remaining
+    }
+}
+
+//@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@
+// Tests
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deck::Deck;
+
+    #[test]
+    fn balanced_systems_come_back_to_zero() {
+        for name in CountingSystem::PRESETS {
+            let system = CountingSystem::preset(name).unwrap();
+            let mut counter = system.counter(1);
+            Deck::new()
+                .cards()
+                .iter()
+                .for_each(|card| counter.count(*card));
+            assert_eq!(counter.running_count(), system.deck_total(), "{}", name);
+            assert_eq!(system.is_balanced(), name != "ko", "{}", name);
+        }
+        assert_eq!(CountingSystem::knock_out().deck_total(), 4.0);
+    }
+
+    #[test]
+    fn true_count_is_per_deck_left() {
+        let card = |rank| Card::new(rank, crate::card::Suit::CLUBS);
+        let mut counter = CountingSystem::hi_lo().counter(2);
+        // Thirteen low cards leave 91 cards, 1.75 decks, at a running count of +13
+        (0..13).for_each(|_| counter.count(card(Rank::FIVE)));
+        assert_eq!(counter.running_count(), 13.0);
+        assert_eq!(counter.true_count(), 13.0 / 1.75);
+
+        // KO's tags add up to +4 a deck, so a quarter of a deck is expected
+        // to bring its count up by 1
+        let mut counter = CountingSystem::knock_out().counter(1);
+        (0..13).for_each(|_| counter.count(card(Rank::EIGHT)));
+        assert_eq!(counter.true_count(), -1.0 / 0.75);
+        counter.reset();
+        assert_eq!(counter.cards_seen(), 0);
+    }
+
+    #[test]
+    fn custom_systems_from_toml() {
+        let text = "name = \"Red Seven\"\n[tags]\nA = -1\n2 = 1\n3 = 1\n4 = 1\n5 = 1\n6 = 1\n7 = 0.5\nT = -1\n";
+        let system = CountingSystem::from_toml(text).unwrap();
+        assert_eq!(system.name(), "Red Seven");
+        assert_eq!(system.tag(Rank::SEVEN), 0.5);
+        assert_eq!(system.tag(Rank::KING), -1.0);
+        assert_eq!(system.tag(Rank::NINE), 0.0);
+        assert_eq!(
+            system.to_string(),
+            "Red Seven (A:-1 2:+1 3:+1 4:+1 5:+1 6:+1 7:+0.5 8:0 9:0 T:-1)"
+        );
+
+        let error = CountingSystem::from_toml("name = \"x\"\n[tags]\nB = 1\n").unwrap_err();
+        assert_eq!(error.to_string(), "unknown rank 'B' in tags");
+    }
+}
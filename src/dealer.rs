@@ -0,0 +1,277 @@
+//%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+// Exact probabilities of how the dealer's hand ends up
+//
+use crate::card::Rank;
+use crate::composition::Composition;
+use crate::rules::{DealerRule, RuleSet};
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{BuildHasherDefault, Hasher};
+
+//#############################################################################
+// The chance of the dealer finishing on each total
+//
+// Totals are those the dealer stands on, which is 17 to 21 unless the shoe
+// runs out of cards. A blackjack is kept apart from a 21 made with more
+// cards.
+//
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DealerProbabilities {
+    totals: [f64; 22],
+    pub blackjack: f64,
+    pub bust: f64,
+}
+
+impl DealerProbabilities {
+    fn new() -> Self {
+        Self {
+            totals: [0.0; 22],
+            blackjack: 0.0,
+            bust: 0.0,
+        }
+    }
+
+    /// The chance of the dealer standing on the total without a blackjack.
+    pub fn total(&self, total: u8) -> f64 {
+        self.totals.get(total as usize).copied().unwrap_or(0.0)
+    }
+
+    /// The chances once the dealer has peeked and doesn't have blackjack.
+    /// All zero if the dealer can't be without one.
+    pub fn given_no_blackjack(&self) -> Self {
+        let mut given = Self::new();
+        if self.blackjack >= 1.0 {
+            return given;
+        }
+        let scale = 1.0 / (1.0 - self.blackjack);
+        given.totals = self.totals.map(|chance| chance * scale);
+        given.bust = self.bust * scale;
+        given
+    }
+
+    /// The chances of all outcomes added up, which should be 1.
+    pub fn sum(&self) -> f64 {
+        self.totals.iter().sum::<f64>() + self.blackjack + self.bust
+    }
+
+    fn add_scaled(&mut self, other: &Self, scale: f64) {
+        for (total, chance) in self.totals.iter_mut().zip(other.totals) {
+            *total += chance * scale;
+        }
+        self.blackjack += other.blackjack * scale;
+        self.bust += other.bust * scale;
+    }
+}
+
+// Shown as percentages, e.g. "17: 14.58%, 18: 13.82%, ... bust: 28.16%"
+impl fmt::Display for DealerProbabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for total in 0..=21 {
+            if self.totals[total] > 0.0 {
+                write!(f, "{}: {:.2}%, ", total, self.totals[total] * 100.0)?;
+            }
+        }
+        write!(
+            f,
+            "blackjack: {:.2}%, bust: {:.2}%",
+            self.blackjack * 100.0,
+            self.bust * 100.0
+        )
+    }
+}
+
+//#############################################################################
+// Work out the dealer's chances for an upcard
+//
+// `shoe` is the cards left to be drawn, so it must not include the upcard or
+// any other cards already dealt. Every way the dealer can draw is followed
+// exactly. The cards drawn so far decide both the dealer's total and what is
+// left in the shoe, so results are remembered by the cards drawn and each
+// reachable hand is only worked out once.
+//
+pub fn dealer_probabilities(
+    rules: &RuleSet,
+    upcard: Rank,
+    shoe: &Composition,
+) -> DealerProbabilities {
+    dealer_probabilities_by_value(rules, upcard.blackjack_value(), shoe.by_value())
+}
+
+/// As dealer_probabilities(), with the upcard and shoe given by blackjack
+/// value as in Composition::by_value().
+pub(crate) fn dealer_probabilities_by_value(
+    rules: &RuleSet,
+    upcard: u8,
+    shoe: [u32; 10],
+) -> DealerProbabilities {
+    let mut calculator = DealerCalculator {
+        hit_soft_17: rules.dealer == DealerRule::HitSoft17,
+        shoe,
+        drawn: 0,
+        memo: FastMap::default(),
+    };
+    calculator.draw(upcard as u32, upcard == 1, 1)
+}
+
+//#############################################################################
+// A quick hash for the small keys the calculators remember results by
+//
+// The standard hasher guards against keys chosen to collide, which isn't a
+// worry here, and takes up most of the time otherwise.
+//
+#[derive(Default)]
+pub(crate) struct FastHasher(u64);
+
+impl Hasher for FastHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        bytes.iter().for_each(|byte| self.write_u64(*byte as u64));
+    }
+
+    fn write_u8(&mut self, value: u8) {
+        self.write_u64(value as u64);
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.write_u64(value as u64);
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.0 = (self.0.rotate_left(5) ^ value).wrapping_mul(0x51_7c_c1_b7_27_22_0a_95);
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+pub(crate) type FastMap<K, V> = HashMap<K, V, BuildHasherDefault<FastHasher>>;
+
+struct DealerCalculator {
+    hit_soft_17: bool,
+    shoe: [u32; 10],
+    // The cards drawn so far, five bits for the count of each value
+    drawn: u64,
+    memo: FastMap<u64, DealerProbabilities>,
+}
+
+impl DealerCalculator {
+    /// The chances for a hand the dealer has to draw to, given by its hard
+    /// total, whether it holds an ace and how many cards it has.
+    fn draw(&mut self, hard: u32, has_ace: bool, cards: u32) -> DealerProbabilities {
+        if let Some(known) = self.memo.get(&self.drawn) {
+            return *known;
+        }
+
+        let mut result = DealerProbabilities::new();
+        let remaining: u32 = self.shoe.iter().sum();
+        for index in 0..10 {
+            let count = self.shoe[index];
+            if count == 0 {
+                continue;
+            }
+            let chance = count as f64 / remaining as f64;
+            let hard = hard + index as u32 + 1;
+            let has_ace = has_ace || index == 0;
+            let soft = has_ace && hard + 10 <= 21;
+            let total = if soft { hard + 10 } else { hard };
+
+            if cards == 1 && total == 21 {
+                result.blackjack += chance;
+            } else if total > 21 {
+                result.bust += chance;
+            } else if total > 17 || (total == 17 && !(soft && self.hit_soft_17)) || remaining == 1 {
+                result.totals[total as usize] += chance;
+            } else {
+                self.shoe[index] -= 1;
+                self.drawn += 1 << (5 * index);
+                let next = self.draw(hard, has_ace, cards + 1);
+                self.drawn -= 1 << (5 * index);
+                self.shoe[index] += 1;
+                result.add_scaled(&next, chance);
+            }
+        }
+
+        self.memo.insert(self.drawn, result);
+        result
+    }
+}
+
+//@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@
+// Tests
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLOSE: f64 = 1e-12;
+
+    #[test]
+    fn chances_add_up() {
+        let rules = RuleSet::default();
+        let shoe = Composition::decks(6);
+        for upcard in Rank::iterator() {
+            let mut left = shoe;
+            left.remove(*upcard);
+            let chances = dealer_probabilities(&rules, *upcard, &left);
+            assert!((chances.sum() - 1.0).abs() < CLOSE, "{:?}", upcard);
+        }
+    }
+
+    #[test]
+    fn six_busts_most() {
+        // Six decks, S17: the dealer busts about 42.3% of the time with a 6 up
+        let mut shoe = Composition::decks(6);
+        shoe.remove(Rank::SIX);
+        let chances = dealer_probabilities(&RuleSet::default(), Rank::SIX, &shoe);
+        assert!((chances.bust - 0.4228).abs() < 0.001, "{}", chances.bust);
+        assert_eq!(chances.blackjack, 0.0);
+    }
+
+    #[test]
+    fn follows_the_cards_exactly() {
+        // Only sevens left: a king up always makes 17 and an ace soft 18
+        let mut sevens = Composition::new();
+        (0..4).for_each(|_| sevens.add(Rank::SEVEN));
+        let rules = RuleSet::default();
+        assert_eq!(
+            dealer_probabilities(&rules, Rank::KING, &sevens).total(17),
+            1.0
+        );
+        assert_eq!(
+            dealer_probabilities(&rules, Rank::ACE, &sevens).total(18),
+            1.0
+        );
+
+        // One ten and one ace left behind an ace: blackjack half the time
+        let mut shoe = Composition::new();
+        shoe.add(Rank::TEN);
+        shoe.add(Rank::ACE);
+        let chances = dealer_probabilities(&rules, Rank::ACE, &shoe);
+        assert_eq!(chances.blackjack, 0.5);
+        assert_eq!(chances.given_no_blackjack().blackjack, 0.0);
+        assert!((chances.given_no_blackjack().sum() - 1.0).abs() < CLOSE);
+
+        // Only tens behind an ace: there is no hand without a blackjack
+        let mut tens = Composition::new();
+        (0..4).for_each(|_| tens.add(Rank::TEN));
+        let chances = dealer_probabilities(&rules, Rank::ACE, &tens);
+        assert_eq!(chances.blackjack, 1.0);
+        assert_eq!(chances.given_no_blackjack().sum(), 0.0);
+    }
+
+    #[test]
+    fn hitting_soft_17_changes_the_odds() {
+        // With the four left in the shoe, A+6 stands on S17 but not on H17
+        let mut shoe = Composition::new();
+        shoe.add(Rank::SIX);
+        shoe.add(Rank::FOUR);
+        let s17 = RuleSet::default();
+        let h17 = RuleSet {
+            dealer: DealerRule::HitSoft17,
+            ..RuleSet::default()
+        };
+        assert_eq!(dealer_probabilities(&s17, Rank::ACE, &shoe).total(17), 0.5);
+        // On H17 the six and the four make 21 whichever comes first
+        assert_eq!(dealer_probabilities(&h17, Rank::ACE, &shoe).total(21), 1.0);
+    }
+}
@@ -0,0 +1,122 @@
+//%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+// The deck of cards that is dealt from
+//
+use crate::card::{Card, Cards, Rank, Suit};
+use rand::prelude::SliceRandom;
+use rand::{thread_rng, Rng};
+
+//#############################################################################
+// Anything that cards can be dealt from, such as a deck or a shoe
+//
+pub trait CardSource {
+    /// Take the top card, or None if there are no cards left.
+    fn draw_card(&mut self) -> Option<Card>;
+}
+
+//#############################################################################
+// The deck of cards that are used to deal to the players from
+//
+#[derive(Debug)]
+pub struct Deck {
+    cards: Cards,
+}
+
+impl Deck {
+    /// Create an unshuffled deck of 52 cards.
+    pub fn new() -> Self {
+        let mut cards = Cards::new();
+        for s in Suit::iterator() {
+            for r in Rank::iterator() {
+                cards.push(Card::new(*r, *s));
+            }
+        }
+        Self { cards }
+    }
+
+    /// Create a deck from the given cards. The last card is the top of the deck
+    /// and is drawn first.
+    pub fn from_cards(cards: Cards) -> Self {
+        Self { cards }
+    }
+
+    /// The cards left in the deck.
+    pub fn cards(&self) -> &Cards {
+        &self.cards
+    }
+
+    pub fn shuffle(&mut self) {
+        self.shuffle_with(&mut thread_rng());
+    }
+
+    /// Shuffle using the given random number generator. A seeded generator
+    /// always produces the same order, which makes games reproducible.
+    pub fn shuffle_with<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        self.cards.shuffle(rng);
+    }
+
+    pub fn draw_card(&mut self) -> Option<Card> {
+        self.cards.pop()
+    }
+
+    pub fn number_of_cards(&self) -> usize {
+        self.cards.len()
+    }
+}
+
+impl CardSource for Deck {
+    fn draw_card(&mut self) -> Option<Card> {
+        Deck::draw_card(self)
+    }
+}
+
+impl Default for Deck {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@
+// Tests
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn deck_has_52_cards() {
+        let deck = Deck::new();
+        assert_eq!(deck.number_of_cards(), 52);
+    }
+
+    #[test]
+    fn deck_has_been_shuffled() {
+        let mut deck = Deck::new();
+        assert_eq!(deck.cards[0], Card::new(Rank::ACE, Suit::HEARTS));
+        // Note: Seeded so that the shuffle is the same every run
+        deck.shuffle_with(&mut ChaCha8Rng::seed_from_u64(1));
+        assert_ne!(deck.cards[0], Card::new(Rank::ACE, Suit::HEARTS));
+        assert_eq!(deck.number_of_cards(), 52);
+    }
+
+    #[test]
+    fn same_seed_gives_same_order() {
+        let mut first = Deck::new();
+        let mut second = Deck::new();
+        first.shuffle_with(&mut ChaCha8Rng::seed_from_u64(42));
+        second.shuffle_with(&mut ChaCha8Rng::seed_from_u64(42));
+        assert_eq!(first.cards, second.cards);
+
+        let mut third = Deck::new();
+        third.shuffle_with(&mut ChaCha8Rng::seed_from_u64(43));
+        assert_ne!(first.cards, third.cards);
+    }
+
+    #[test]
+    fn drawing_card_removes_from_deck() {
+        let mut deck = Deck::new();
+        deck.draw_card();
+        assert_eq!(deck.number_of_cards(), 51);
+    }
+}
@@ -0,0 +1,650 @@
+//%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+// Playing deviations, changes to a strategy when the true count passes an
+// index
+//
+use crate::card::{Card, Cards};
+use crate::chart::{Chart, ChartAction, ChartError};
+use crate::game::Action;
+use crate::hand::HandValue;
+use crate::rules::RuleSet;
+use crate::strategy::Strategy;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+//#############################################################################
+// The hand a deviation is for
+//
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DeviationHand {
+    Hard(u8),
+    Soft(u8),
+    /// A pair of cards of the value, 11 for aces.
+    Pair(u8),
+    /// The offer of insurance, against an ace.
+    Insurance,
+}
+
+impl DeviationHand {
+    /// Does the deviation cover the hand? Pairs only when they may be split.
+    fn covers(&self, hand: &[Card], can_split: bool) -> bool {
+        let value = HandValue::of(hand);
+        match *self {
+            DeviationHand::Hard(total) => !value.soft && value.total == total,
+            DeviationHand::Soft(total) => value.soft && value.total == total,
+            DeviationHand::Pair(pair) => {
+                can_split
+                    && hand.len() == 2
+                    && Chart::card_value(hand[0].rank) == pair
+                    && Chart::card_value(hand[1].rank) == pair
+            }
+            DeviationHand::Insurance => false,
+        }
+    }
+
+    fn parse(label: &str) -> Option<Self> {
+        let total = |text: &str| text.parse().ok();
+        if label.eq_ignore_ascii_case("insurance") {
+            Some(DeviationHand::Insurance)
+        } else if let Some(soft) = label.strip_prefix("soft ") {
+            total(soft)
+                .filter(|total| Chart::SOFT_TOTALS.contains(total))
+                .map(DeviationHand::Soft)
+        } else if let Some((first, second)) = label.split_once('-') {
+            let card = parse_card_value(first)?;
+            (parse_card_value(second) == Some(card)).then_some(DeviationHand::Pair(card))
+        } else {
+            total(label)
+                .filter(|total| Chart::HARD_TOTALS.contains(total))
+                .map(DeviationHand::Hard)
+        }
+    }
+}
+
+// Shown as in index files, e.g. "16", "soft 18", "10-10" or "insurance"
+impl fmt::Display for DeviationHand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeviationHand::Hard(total) => write!(f, "{}", total),
+            DeviationHand::Soft(total) => write!(f, "soft {}", total),
+            DeviationHand::Pair(card) => {
+                write!(f, "{}-{}", card_value_label(*card), card_value_label(*card))
+            }
+            DeviationHand::Insurance => write!(f, "insurance"),
+        }
+    }
+}
+
+fn card_value_label(value: u8) -> String {
+    match value {
+        11 => "A".to_string(),
+        value => value.to_string(),
+    }
+}
+
+fn parse_card_value(label: &str) -> Option<u8> {
+    match label {
+        "A" => Some(11),
+        label => label.parse().ok().filter(|value| (2..=10).contains(value)),
+    }
+}
+
+//#############################################################################
+// When a deviation is made, at or above an index or below it
+//
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Threshold {
+    AtOrAbove(f64),
+    Below(f64),
+}
+
+impl Threshold {
+    pub fn is_met(&self, true_count: f64) -> bool {
+        match *self {
+            Threshold::AtOrAbove(index) => true_count >= index,
+            Threshold::Below(index) => true_count < index,
+        }
+    }
+
+    pub fn index(&self) -> f64 {
+        match *self {
+            Threshold::AtOrAbove(index) | Threshold::Below(index) => index,
+        }
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        let number = |text: &str| {
+            text.trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|index| index.is_finite())
+        };
+        if let Some(index) = text.strip_prefix(">=") {
+            number(index).map(Threshold::AtOrAbove)
+        } else if let Some(index) = text.strip_prefix('<') {
+            number(index).map(Threshold::Below)
+        } else {
+            None
+        }
+    }
+}
+
+// Shown as in index files, e.g. ">=+3" or "<-1"
+impl fmt::Display for Threshold {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Threshold::AtOrAbove(index) => write!(f, ">={:+}", index),
+            Threshold::Below(index) => write!(f, "<{:+}", index),
+        }
+    }
+}
+
+//#############################################################################
+// What to do instead: a chart cell for a hand, or whether to take insurance
+//
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DeviationAction {
+    Play(ChartAction),
+    Insure(bool),
+}
+
+impl DeviationAction {
+    fn code(&self) -> &'static str {
+        match self {
+            DeviationAction::Play(action) => action.code(),
+            DeviationAction::Insure(true) => "Y",
+            DeviationAction::Insure(false) => "N",
+        }
+    }
+}
+
+//#############################################################################
+// A single deviation, e.g. stand on 16 against a 10 at a true count of 0 or
+// more
+//
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Deviation {
+    pub hand: DeviationHand,
+    /// The dealer's upcard, 2 to 11 for an ace.
+    pub upcard: u8,
+    pub threshold: Threshold,
+    pub action: DeviationAction,
+}
+
+impl Deviation {
+    fn play(hand: DeviationHand, upcard: u8, threshold: Threshold, code: &str) -> Self {
+        Self {
+            hand,
+            upcard,
+            threshold,
+            action: DeviationAction::Play(ChartAction::from_code(code).unwrap()),
+        }
+    }
+}
+
+// Shown as e.g. "16 vs 10: S at >=+0" or "insurance: Y at >=+3"
+impl fmt::Display for Deviation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.hand == DeviationHand::Insurance {
+            write!(f, "insurance")?;
+        } else {
+            write!(f, "{} vs {}", self.hand, card_value_label(self.upcard))?;
+        }
+        write!(f, ": {} at {}", self.action.code(), self.threshold)
+    }
+}
+
+//#############################################################################
+// A table of deviations
+//
+// The first deviation in the table that covers a hand and whose index is
+// passed is the one made, so a table should list the more important ones
+// first. A deviation whose first choice isn't allowed, such as doubling
+// three cards, is passed over.
+//
+// Index files are CSV, a header and then a row per deviation giving the
+// hand, upcard, threshold and chart code:
+//
+// hand,upcard,index,action
+// insurance,A,>=3,Y
+// 16,10,>=0,S
+// 13,2,<-1,H
+//
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DeviationTable {
+    deviations: Vec<Deviation>,
+}
+
+impl DeviationTable {
+    /// The names of the built in tables, as accepted by preset().
+    pub const PRESETS: [&'static str; 2] = ["illustrious-18", "fab-4"];
+
+    pub fn new(deviations: Vec<Deviation>) -> Self {
+        Self { deviations }
+    }
+
+    /// A built in table by name.
+    pub fn preset(name: &str) -> Option<DeviationTable> {
+        match name {
+            "illustrious-18" => Some(Self::illustrious_18()),
+            "fab-4" => Some(Self::fab_4()),
+            _ => None,
+        }
+    }
+
+    /// The 18 deviations worth the most with Hi-Lo in a shoe game, in order
+    /// of how much they are worth.
+    pub fn illustrious_18() -> Self {
+        use DeviationHand::{Hard, Pair};
+        use Threshold::{AtOrAbove, Below};
+        let insurance = Deviation {
+            hand: DeviationHand::Insurance,
+            upcard: 11,
+            threshold: AtOrAbove(3.0),
+            action: DeviationAction::Insure(true),
+        };
+        Self::new(vec![
+            insurance,
+            Deviation::play(Hard(16), 10, AtOrAbove(0.0), "S"),
+            Deviation::play(Hard(15), 10, AtOrAbove(4.0), "S"),
+            Deviation::play(Pair(10), 5, AtOrAbove(5.0), "P"),
+            Deviation::play(Pair(10), 6, AtOrAbove(4.0), "P"),
+            Deviation::play(Hard(10), 10, AtOrAbove(4.0), "D"),
+            Deviation::play(Hard(12), 3, AtOrAbove(2.0), "S"),
+            Deviation::play(Hard(12), 2, AtOrAbove(3.0), "S"),
+            Deviation::play(Hard(11), 11, AtOrAbove(1.0), "D"),
+            Deviation::play(Hard(9), 2, AtOrAbove(1.0), "D"),
+            Deviation::play(Hard(10), 11, AtOrAbove(4.0), "D"),
+            Deviation::play(Hard(9), 7, AtOrAbove(3.0), "D"),
+            Deviation::play(Hard(16), 9, AtOrAbove(5.0), "S"),
+            Deviation::play(Hard(13), 2, Below(-1.0), "H"),
+            Deviation::play(Hard(12), 4, Below(0.0), "H"),
+            Deviation::play(Hard(12), 5, Below(-2.0), "H"),
+            Deviation::play(Hard(12), 6, Below(-1.0), "H"),
+            Deviation::play(Hard(13), 3, Below(-2.0), "H"),
+        ])
+    }
+
+    /// The four surrender deviations worth the most with Hi-Lo when late
+    /// surrender is allowed.
+    pub fn fab_4() -> Self {
+        use DeviationHand::Hard;
+        use Threshold::AtOrAbove;
+        Self::new(vec![
+            Deviation::play(Hard(14), 10, AtOrAbove(3.0), "Rh"),
+            Deviation::play(Hard(15), 10, AtOrAbove(0.0), "Rh"),
+            Deviation::play(Hard(15), 9, AtOrAbove(2.0), "Rh"),
+            Deviation::play(Hard(15), 11, AtOrAbove(1.0), "Rh"),
+        ])
+    }
+
+    pub fn deviations(&self) -> &[Deviation] {
+        &self.deviations
+    }
+
+    pub fn len(&self) -> usize {
+        self.deviations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deviations.is_empty()
+    }
+
+    /// Add another table's deviations after this one's.
+    pub fn extend(&mut self, other: &DeviationTable) {
+        self.deviations.extend_from_slice(&other.deviations);
+    }
+
+    /// The table with one of its deviations left out.
+    pub fn without(&self, index: usize) -> DeviationTable {
+        let mut table = self.clone();
+        table.deviations.remove(index);
+        table
+    }
+
+    /// The action for a hand at the true count, given what would be done
+    /// without deviations. A hand that would be split or surrendered is only
+    /// played differently by a deviation for splitting or surrendering it.
+    /// A surrender deviation whose index isn't reached plays its second
+    /// choice instead, so "Rh" at 0 or more also means hit below 0.
+    pub fn apply(
+        &self,
+        hand: &[Card],
+        upcard: Card,
+        allowed: &[Action],
+        rules: &RuleSet,
+        true_count: f64,
+        base: Action,
+    ) -> Action {
+        let upcard = Chart::card_value(upcard.rank);
+        let can_split = allowed.contains(&Action::Split);
+        let without_surrender: Vec<Action> = allowed
+            .iter()
+            .copied()
+            .filter(|action| *action != Action::Surrender)
+            .collect();
+        self.deviations
+            .iter()
+            .find_map(|deviation| {
+                let DeviationAction::Play(action) = deviation.action else {
+                    return None;
+                };
+                let first_choice = action.first_choice(rules);
+                if deviation.upcard != upcard
+                    || !deviation.hand.covers(hand, can_split)
+                    || !allowed.contains(&first_choice)
+                {
+                    return None;
+                }
+                let met = deviation.threshold.is_met(true_count);
+                match base {
+                    Action::Split if !matches!(deviation.hand, DeviationHand::Pair(_)) => None,
+                    Action::Surrender if first_choice != Action::Surrender => None,
+                    Action::Surrender if !met => Some(action.resolve(&without_surrender, rules)),
+                    _ => met.then_some(first_choice),
+                }
+            })
+            .unwrap_or(base)
+    }
+
+    /// Whether to take insurance at the true count, if a deviation says.
+    pub fn insure(&self, true_count: f64) -> Option<bool> {
+        self.deviations
+            .iter()
+            .find_map(|deviation| match deviation.action {
+                DeviationAction::Insure(take) if deviation.threshold.is_met(true_count) => {
+                    Some(take)
+                }
+                _ => None,
+            })
+    }
+
+    /// Read a table from CSV.
+    pub fn from_csv(text: &str) -> Result<DeviationTable, ChartError> {
+        let mut deviations = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let parse_error = |reason: String| ChartError::Parse {
+                line: index + 1,
+                reason,
+            };
+            let cells: Vec<&str> = line
+                .split(',')
+                .map(|cell| cell.trim().trim_matches('"').trim())
+                .collect();
+            if cells.iter().all(|cell| cell.is_empty()) || cells[0].eq_ignore_ascii_case("hand") {
+                continue;
+            }
+            if cells.len() != 4 {
+                return Err(parse_error(format!(
+                    "expected hand, upcard, index and action but found {} cells",
+                    cells.len()
+                )));
+            }
+
+            let hand = DeviationHand::parse(cells[0])
+                .ok_or_else(|| parse_error(format!("unknown hand '{}'", cells[0])))?;
+            let upcard = parse_card_value(cells[1])
+                .ok_or_else(|| parse_error(format!("unknown upcard '{}'", cells[1])))?;
+            let threshold = Threshold::parse(cells[2]).ok_or_else(|| {
+                parse_error(format!(
+                    "index '{}' should be >= or < a true count",
+                    cells[2]
+                ))
+            })?;
+            let action = match (hand, cells[3]) {
+                (DeviationHand::Insurance, "Y") => DeviationAction::Insure(true),
+                (DeviationHand::Insurance, "N") => DeviationAction::Insure(false),
+                (DeviationHand::Insurance, code) => {
+                    return Err(parse_error(format!(
+                        "insurance takes Y or N, not '{}'",
+                        code
+                    )))
+                }
+                (_, code) => {
+                    DeviationAction::Play(ChartAction::from_code(code).ok_or_else(|| {
+                        parse_error(format!("unknown code '{}' for {}", code, cells[0]))
+                    })?)
+                }
+            };
+            if hand == DeviationHand::Insurance && upcard != 11 {
+                return Err(parse_error(
+                    "insurance is only offered against an ace".to_string(),
+                ));
+            }
+            deviations.push(Deviation {
+                hand,
+                upcard,
+                threshold,
+                action,
+            });
+        }
+        Ok(Self::new(deviations))
+    }
+
+    /// Write the table as CSV in the form read by from_csv().
+    pub fn to_csv(&self) -> String {
+        let mut text = "hand,upcard,index,action\n".to_string();
+        for deviation in &self.deviations {
+            text.push_str(&format!(
+                "{},{},{},{}\n",
+                deviation.hand,
+                card_value_label(deviation.upcard),
+                deviation.threshold,
+                deviation.action.code()
+            ));
+        }
+        text
+    }
+
+    /// Load a table from a CSV file.
+    pub fn load(path: impl AsRef<Path>) -> Result<DeviationTable, ChartError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|error| ChartError::Io(path.display().to_string(), error))?;
+        Self::from_csv(&text)
+    }
+
+    /// Save the table to a CSV file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ChartError> {
+        let path = path.as_ref();
+        fs::write(path, self.to_csv())
+            .map_err(|error| ChartError::Io(path.display().to_string(), error))
+    }
+}
+
+//#############################################################################
+// A strategy that makes the deviations in a table, and otherwise plays as
+// another strategy does
+//
+// Without a true count it plays just as the other strategy.
+//
+#[derive(Clone, Debug)]
+pub struct DeviationStrategy<S> {
+    base: S,
+    deviations: DeviationTable,
+}
+
+impl<S: Strategy> DeviationStrategy<S> {
+    pub fn new(base: S, deviations: DeviationTable) -> Self {
+        Self { base, deviations }
+    }
+
+    pub fn deviations(&self) -> &DeviationTable {
+        &self.deviations
+    }
+}
+
+impl<S: Strategy> Strategy for DeviationStrategy<S> {
+    fn decide(&self, hand: &Cards, upcard: Card, allowed: &[Action], rules: &RuleSet) -> Action {
+        self.base.decide(hand, upcard, allowed, rules)
+    }
+
+    fn take_insurance(&self, hand: &Cards, rules: &RuleSet) -> bool {
+        self.base.take_insurance(hand, rules)
+    }
+
+    fn decide_at_count(
+        &self,
+        hand: &Cards,
+        upcard: Card,
+        allowed: &[Action],
+        rules: &RuleSet,
+        true_count: f64,
+    ) -> Action {
+        let base = self
+            .base
+            .decide_at_count(hand, upcard, allowed, rules, true_count);
+        self.deviations
+            .apply(hand, upcard, allowed, rules, true_count, base)
+    }
+
+    fn take_insurance_at_count(&self, hand: &Cards, rules: &RuleSet, true_count: f64) -> bool {
+        self.deviations
+            .insure(true_count)
+            .unwrap_or_else(|| self.base.take_insurance_at_count(hand, rules, true_count))
+    }
+}
+
+//@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@
+// Tests
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::{Rank, Suit};
+    use crate::rules::SurrenderRule;
+    use crate::strategy::BasicStrategy;
+
+    fn cards(ranks: &[Rank]) -> Cards {
+        Cards(
+            ranks
+                .iter()
+                .map(|rank| Card::new(*rank, Suit::CLUBS))
+                .collect(),
+        )
+    }
+
+    fn upcard(rank: Rank) -> Card {
+        Card::new(rank, Suit::HEARTS)
+    }
+
+    const ALL: [Action; 5] = [
+        Action::Hit,
+        Action::Stand,
+        Action::Double,
+        Action::Split,
+        Action::Surrender,
+    ];
+    const NO_SURRENDER: [Action; 4] = [Action::Hit, Action::Stand, Action::Double, Action::Split];
+
+    #[test]
+    fn illustrious_18_by_the_count() {
+        let rules = RuleSet::default();
+        let strategy =
+            DeviationStrategy::new(BasicStrategy::new(&rules), DeviationTable::illustrious_18());
+        let decide = |hand: &[Rank], up, count| {
+            strategy.decide_at_count(&cards(hand), upcard(up), &NO_SURRENDER, &rules, count)
+        };
+        let ten_six = [Rank::TEN, Rank::SIX];
+        assert_eq!(decide(&ten_six, Rank::KING, -0.5), Action::Hit);
+        assert_eq!(decide(&ten_six, Rank::KING, 0.0), Action::Stand);
+        assert_eq!(
+            strategy.decide(&cards(&ten_six), upcard(Rank::KING), &NO_SURRENDER, &rules),
+            Action::Hit
+        );
+        assert_eq!(
+            decide(&[Rank::TEN, Rank::TWO], Rank::FOUR, -0.5),
+            Action::Hit
+        );
+        assert_eq!(
+            decide(&[Rank::TEN, Rank::KING], Rank::SIX, 4.0),
+            Action::Split
+        );
+        assert_eq!(
+            decide(&[Rank::TEN, Rank::KING], Rank::SIX, 3.9),
+            Action::Stand
+        );
+        // 8-8 is split, not played as a 16
+        assert_eq!(
+            decide(&[Rank::EIGHT, Rank::EIGHT], Rank::TEN, 5.0),
+            Action::Split
+        );
+        // Doubling ten against a ten needs two cards
+        let three_cards = cards(&[Rank::FIVE, Rank::THREE, Rank::TWO]);
+        let hit_stand = [Action::Hit, Action::Stand];
+        assert_eq!(
+            strategy.decide_at_count(&three_cards, upcard(Rank::TEN), &hit_stand, &rules, 5.0),
+            Action::Hit
+        );
+
+        assert!(strategy.take_insurance_at_count(&cards(&ten_six), &rules, 3.0));
+        assert!(!strategy.take_insurance_at_count(&cards(&ten_six), &rules, 2.9));
+        assert!(!strategy.take_insurance(&cards(&ten_six), &rules));
+    }
+
+    #[test]
+    fn fab_4_surrenders() {
+        let rules = RuleSet {
+            surrender: SurrenderRule::Late,
+            ..RuleSet::default()
+        };
+        let mut deviations = DeviationTable::fab_4();
+        deviations.extend(&DeviationTable::illustrious_18());
+        let strategy = DeviationStrategy::new(BasicStrategy::new(&rules), deviations);
+        let decide = |hand: &[Rank], up, count| {
+            strategy.decide_at_count(&cards(hand), upcard(up), &ALL, &rules, count)
+        };
+        assert_eq!(
+            decide(&[Rank::TEN, Rank::FOUR], Rank::TEN, 3.0),
+            Action::Surrender
+        );
+        assert_eq!(
+            decide(&[Rank::TEN, Rank::FOUR], Rank::TEN, 2.0),
+            Action::Hit
+        );
+        // Basic strategy surrenders 15 against a ten, but not below a count of 0
+        assert_eq!(
+            decide(&[Rank::TEN, Rank::FIVE], Rank::TEN, 0.0),
+            Action::Surrender
+        );
+        assert_eq!(
+            decide(&[Rank::TEN, Rank::FIVE], Rank::TEN, -1.0),
+            Action::Hit
+        );
+        // Standing on 16 doesn't stop it being surrendered
+        assert_eq!(
+            decide(&[Rank::TEN, Rank::SIX], Rank::TEN, 2.0),
+            Action::Surrender
+        );
+    }
+
+    #[test]
+    fn index_files() {
+        let mut table = DeviationTable::illustrious_18();
+        table.extend(&DeviationTable::fab_4());
+        table.extend(&DeviationTable::new(vec![Deviation::play(
+            DeviationHand::Soft(19),
+            6,
+            Threshold::AtOrAbove(1.0),
+            "Ds",
+        )]));
+        let text = table.to_csv();
+        assert!(text.starts_with("hand,upcard,index,action\ninsurance,A,>=+3,Y\n16,10,>=+0,S\n"));
+        assert!(text.contains("\n10-10,5,>=+5,P\n"));
+        assert!(text.contains("\n13,3,<-2,H\n"));
+        assert!(text.ends_with("\nsoft 19,6,>=+1,Ds\n"));
+        assert_eq!(DeviationTable::from_csv(&text).unwrap(), table);
+        assert_eq!(table.deviations()[1].to_string(), "16 vs 10: S at >=+0");
+
+        let error = DeviationTable::from_csv("hand,upcard,index,action\n16,10,0,S\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "line 2: index '0' should be >= or < a true count"
+        );
+        let error = DeviationTable::from_csv("insurance,10,>=3,Y\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "line 1: insurance is only offered against an ace"
+        );
+    }
+}
@@ -0,0 +1,536 @@
+//%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+// Exact expected values of the player's actions
+//
+use crate::card::{Card, Rank, Suit};
+use crate::composition::Composition;
+use crate::dealer::{dealer_probabilities_by_value, DealerProbabilities, FastMap};
+use crate::game::Action;
+use crate::hand::HandValue;
+use crate::rules::{RuleSet, SurrenderRule};
+use std::collections::HashMap;
+use std::fmt;
+
+//#############################################################################
+// The expected value of each action for a hand, as a fraction of the bet
+//
+// Actions that can't be taken with the hand under the rules are None.
+//
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ActionEvs {
+    pub stand: f64,
+    pub hit: f64,
+    pub double: Option<f64>,
+    pub split: Option<f64>,
+    pub surrender: Option<f64>,
+}
+
+impl ActionEvs {
+    /// The expected value of an action, if it can be taken.
+    pub fn get(&self, action: Action) -> Option<f64> {
+        match action {
+            Action::Hit => Some(self.hit),
+            Action::Stand => Some(self.stand),
+            Action::Double => self.double,
+            Action::Split => self.split,
+            Action::Surrender => self.surrender,
+        }
+    }
+
+    /// The action with the highest expected value and that value.
+    pub fn best(&self) -> (Action, f64) {
+        self.best_of(&[
+            Action::Hit,
+            Action::Stand,
+            Action::Double,
+            Action::Split,
+            Action::Surrender,
+        ])
+    }
+
+    /// The best of the given actions, e.g. those allowed in a round.
+    ///
+    /// # Panics
+    ///
+    /// If none of the actions can be taken.
+    pub fn best_of(&self, actions: &[Action]) -> (Action, f64) {
+        actions
+            .iter()
+            .filter_map(|action| self.get(*action).map(|ev| (*action, ev)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .expect("no action can be taken")
+    }
+}
+
+// Shown as each action with its value, e.g. "stand -0.5400, hit -0.5398"
+impl fmt::Display for ActionEvs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "stand {:+.4}, hit {:+.4}", self.stand, self.hit)?;
+        for (action, ev) in [
+            (Action::Double, self.double),
+            (Action::Split, self.split),
+            (Action::Surrender, self.surrender),
+        ] {
+            if let Some(ev) = ev {
+                write!(f, ", {} {:+.4}", action, ev)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+//#############################################################################
+// Work out the expected value of each action for the player's first decision
+//
+// `shoe` is the cards left to be drawn, so it must not include the player's
+// cards or the upcard. Standing, hitting, doubling and surrendering are
+// exact: every card the player and the dealer could draw is followed, and
+// after a hit the player carries on with whichever of hitting and standing
+// is better.
+//
+// When the dealer has peeked for blackjack the values are given that the
+// dealer doesn't have one. This also changes the chance of each card the
+// player draws, since the hole card is known not to be a ten (or an ace).
+// With early surrender the decision comes before the peek, so the values of
+// everything but surrendering include losing the bet to a dealer blackjack.
+// Without a hole card a dealer blackjack takes doubles and splits too.
+//
+// Splitting is close but not exact. Resplits are followed, and the cards of
+// the pair split off are taken out of the shoe for the later hands, but each
+// hand is otherwise worked out as if the other hands drew no cards.
+//
+pub fn action_evs(rules: &RuleSet, hand: &[Card], upcard: Card, shoe: &Composition) -> ActionEvs {
+    EvCalculator::new(rules).action_evs(hand, upcard, shoe)
+}
+
+//#############################################################################
+// Works out expected values for many hands under the same rules
+//
+// Everything worked out is remembered by the cards left in the shoe, so
+// hands that can be reached from each other, such as 10-2 and 10-2-4, share
+// the work. Use one calculator when asking about a lot of hands.
+//
+pub struct EvCalculator {
+    rules: RuleSet,
+    analyzers: HashMap<u8, Analyzer>,
+}
+
+impl EvCalculator {
+    pub fn new(rules: &RuleSet) -> Self {
+        Self {
+            rules: *rules,
+            analyzers: HashMap::new(),
+        }
+    }
+
+    /// The expected value of each action, as for action_evs().
+    pub fn action_evs(&mut self, hand: &[Card], upcard: Card, shoe: &Composition) -> ActionEvs {
+        let rules = self.rules;
+        let upcard_value = upcard.rank.blackjack_value();
+        let shoe_by_value = shoe.by_value();
+        let analyzer = self
+            .analyzers
+            .entry(upcard_value)
+            .or_insert_with(|| Analyzer::new(rules, upcard_value));
+
+        let player = PlayerHand {
+            hard: hand.iter().map(|card| card.rank.blackjack_value()).sum(),
+            has_ace: hand.iter().any(|card| card.rank == Rank::ACE),
+        };
+
+        let natural = HandValue::of(hand).blackjack;
+        let mut evs = ActionEvs {
+            stand: if natural {
+                rules.blackjack_payout.as_f64()
+            } else {
+                analyzer.stand(&shoe_by_value, player)
+            },
+            hit: analyzer.hit(&shoe_by_value, player, false),
+            double: rules
+                .can_double(hand, false)
+                .then(|| analyzer.double(&shoe_by_value, player)),
+            split: rules
+                .can_split(hand, false, 1)
+                .then(|| analyzer.split(&shoe_by_value, hand[0].rank)),
+            surrender: None,
+        };
+
+        if hand.len() == 2 && rules.surrender != SurrenderRule::NotAllowed {
+            evs.surrender = Some(-0.5);
+        }
+
+        // A natural only pushes a dealer blackjack the dealer draws to
+        // without a hole card. When the dealer peeks the natural is paid
+        // given there isn't one, like everything else, except that with
+        // early surrender everything but a natural loses it before the peek.
+        let blackjack_chance = match upcard_value {
+            1 => Some(10),
+            10 => Some(1),
+            _ => None,
+        }
+        .map(|value| shoe_by_value[value as usize - 1] as f64 / shoe.total() as f64)
+        .unwrap_or(0.0);
+        if natural {
+            if analyzer.excluded.is_none() {
+                evs.stand *= 1.0 - blackjack_chance;
+            }
+        } else if analyzer.excluded.is_some()
+            && rules.surrender == SurrenderRule::Early
+            && evs.surrender.is_some()
+        {
+            let before_peek = |ev: f64| -blackjack_chance + (1.0 - blackjack_chance) * ev;
+            evs.stand = before_peek(evs.stand);
+            evs.hit = before_peek(evs.hit);
+            evs.double = evs.double.map(before_peek);
+            evs.split = evs.split.map(before_peek);
+        }
+        evs
+    }
+}
+
+//#############################################################################
+// The player's hand as a hard total and whether it holds an ace
+//
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct PlayerHand {
+    hard: u8,
+    has_ace: bool,
+}
+
+impl PlayerHand {
+    fn total(&self) -> u8 {
+        if self.has_ace && self.hard + 10 <= 21 {
+            self.hard + 10
+        } else {
+            self.hard
+        }
+    }
+
+    fn value(&self) -> HandValue {
+        HandValue {
+            total: self.total(),
+            soft: self.total() != self.hard,
+            blackjack: false,
+        }
+    }
+
+    fn with(&self, value: u8) -> Self {
+        Self {
+            hard: self.hard + value,
+            has_ace: self.has_ace || value == 1,
+        }
+    }
+}
+
+struct Analyzer {
+    rules: RuleSet,
+    upcard: u8,
+    // The value the hole card can't be, once the dealer has peeked
+    excluded: Option<u8>,
+    dealer: FastMap<[u32; 10], DealerProbabilities>,
+    // Value of hitting or standing, whichever is better
+    play: FastMap<([u32; 10], PlayerHand), f64>,
+}
+
+impl Analyzer {
+    fn new(rules: RuleSet, upcard: u8) -> Self {
+        let excluded = match upcard {
+            _ if rules.no_hole_card => None,
+            1 => Some(10),
+            10 => Some(1),
+            _ => None,
+        };
+        Self {
+            rules,
+            upcard,
+            excluded,
+            dealer: FastMap::default(),
+            play: FastMap::default(),
+        }
+    }
+
+    /// The chance of the player drawing each value, allowing for what is
+    /// known about the hole card.
+    fn draw_chances(&self, shoe: &[u32; 10]) -> [f64; 10] {
+        let remaining: u32 = shoe.iter().sum();
+        let mut chances = [0.0; 10];
+        if remaining == 0 {
+            return chances;
+        }
+        match self.excluded {
+            // Only possible with the hole card being some other value
+            Some(excluded) if remaining > 1 && shoe[excluded as usize - 1] < remaining => {
+                let n = remaining as f64;
+                let x = shoe[excluded as usize - 1] as f64;
+                for (index, count) in shoe.iter().enumerate() {
+                    let count = *count as f64;
+                    chances[index] = if index == excluded as usize - 1 {
+                        count / (n - 1.0)
+                    } else {
+                        count * (n - x - 1.0) / ((n - x) * (n - 1.0))
+                    };
+                }
+            }
+            _ => {
+                for (index, count) in shoe.iter().enumerate() {
+                    chances[index] = *count as f64 / remaining as f64;
+                }
+            }
+        }
+        chances
+    }
+
+    /// The value of standing, or -1 if bust.
+    fn stand(&mut self, shoe: &[u32; 10], player: PlayerHand) -> f64 {
+        let total = player.total();
+        if total > 21 {
+            return -1.0;
+        }
+        let (rules, upcard, excluded) = (&self.rules, self.upcard, self.excluded);
+        let dealer = self.dealer.entry(*shoe).or_insert_with(|| {
+            let chances = dealer_probabilities_by_value(rules, upcard, *shoe);
+            if excluded.is_some() {
+                chances.given_no_blackjack()
+            } else {
+                chances
+            }
+        });
+        let mut ev = dealer.bust - dealer.blackjack;
+        for dealer_total in 0..=21 {
+            let chance = dealer.total(dealer_total);
+            if total > dealer_total {
+                ev += chance;
+            } else if total < dealer_total {
+                ev -= chance;
+            }
+        }
+        ev
+    }
+
+    /// The value of taking a card and then playing on as well as possible.
+    /// Split aces that get only one card have to stand.
+    fn hit(&mut self, shoe: &[u32; 10], player: PlayerHand, one_card: bool) -> f64 {
+        let chances = self.draw_chances(shoe);
+        let mut ev = 0.0;
+        let mut next = *shoe;
+        for index in 0..10 {
+            if chances[index] == 0.0 {
+                continue;
+            }
+            next[index] -= 1;
+            let drawn = player.with(index as u8 + 1);
+            ev += chances[index]
+                * if one_card {
+                    self.stand(&next, drawn)
+                } else {
+                    self.play(&next, drawn)
+                };
+            next[index] += 1;
+        }
+        ev
+    }
+
+    /// The better of hitting and standing.
+    fn play(&mut self, shoe: &[u32; 10], player: PlayerHand) -> f64 {
+        if player.total() > 21 {
+            return -1.0;
+        }
+        if let Some(known) = self.play.get(&(*shoe, player)) {
+            return *known;
+        }
+        let stand = self.stand(shoe, player);
+        let ev = if player.total() == 21 {
+            stand
+        } else {
+            stand.max(self.hit(shoe, player, false))
+        };
+        self.play.insert((*shoe, player), ev);
+        ev
+    }
+
+    /// Twice the value of taking exactly one more card.
+    fn double(&mut self, shoe: &[u32; 10], player: PlayerHand) -> f64 {
+        2.0 * self.hit(shoe, player, true)
+    }
+
+    /// The value of splitting the pair, all the hands together. Another card
+    /// of the pair is split again while the rules allow, and each hand split
+    /// off takes its card out of the shoe for the hands after it.
+    fn split(&mut self, shoe: &[u32; 10], rank: Rank) -> f64 {
+        let index = rank.blackjack_value() as usize - 1;
+        let mut shoe = *shoe;
+        let mut by_hands = Vec::new();
+        for hands in 2..=self.rules.max_split_hands as usize {
+            by_hands.push(self.split_hand(&shoe, rank, hands));
+            shoe[index] = shoe[index].saturating_sub(1);
+        }
+
+        // The value of the hands still waiting for their second card, from
+        // the most hands the player can have down to the first split
+        let mut more_hands = vec![0.0; by_hands.len() + 3];
+        for (split_off, (resplit, played)) in by_hands.iter().enumerate().rev() {
+            let mut hands = vec![0.0; by_hands.len() + 3];
+            for waiting in 1..=split_off + 2 {
+                hands[waiting] = (1.0 - resplit) * (played + hands[waiting - 1])
+                    + resplit * more_hands[waiting + 1];
+            }
+            more_hands = hands;
+        }
+        more_hands[2]
+    }
+
+    /// For a hand started from a card of the pair while the player has
+    /// `hands` hands, the chance of it being split again and its value when
+    /// it is played out instead.
+    fn split_hand(&mut self, shoe: &[u32; 10], rank: Rank, hands: usize) -> (f64, f64) {
+        let value = rank.blackjack_value();
+        let start = PlayerHand {
+            hard: 0,
+            has_ace: false,
+        }
+        .with(value);
+        let one_card = value == 1 && self.rules.one_card_split_aces;
+
+        let chances = self.draw_chances(shoe);
+        let mut evs = [0.0; 10];
+        let mut next = *shoe;
+        for index in 0..10 {
+            if chances[index] == 0.0 {
+                continue;
+            }
+            next[index] -= 1;
+            let drawn = start.with(index as u8 + 1);
+            evs[index] = if one_card {
+                self.stand(&next, drawn)
+            } else if self.rules.double_after_split && self.rules.double.allows(drawn.value()) {
+                self.play(&next, drawn).max(self.double(&next, drawn))
+            } else {
+                self.play(&next, drawn)
+            };
+            next[index] += 1;
+        }
+
+        let pair = [Card::new(rank, Suit::CLUBS); 2];
+        let resplit = if self.rules.can_split(&pair, true, hands) {
+            // Unless unlike tens can be split only a ten of the same rank,
+            // about a quarter of them, makes a pair
+            let same_rank = if value == 10 && !self.rules.split_unlike_tens {
+                0.25
+            } else {
+                1.0
+            };
+            chances[value as usize - 1] * same_rank
+        } else {
+            0.0
+        };
+        let ev: f64 = chances
+            .iter()
+            .zip(evs)
+            .map(|(chance, ev)| chance * ev)
+            .sum();
+        let played = if resplit < 1.0 {
+            (ev - resplit * evs[value as usize - 1]) / (1.0 - resplit)
+        } else {
+            0.0
+        };
+        (resplit, played)
+    }
+}
+
+//@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@
+// Tests
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evs(rules: &RuleSet, hand: &[Rank], upcard: Rank) -> ActionEvs {
+        let hand: Vec<Card> = hand
+            .iter()
+            .map(|rank| Card::new(*rank, Suit::CLUBS))
+            .collect();
+        let upcard = Card::new(upcard, Suit::HEARTS);
+        let mut dealt = hand.clone();
+        dealt.push(upcard);
+        let shoe = Composition::decks(rules.decks).without(&dealt);
+        action_evs(rules, &hand, upcard, &shoe)
+    }
+
+    #[test]
+    fn best_plays() {
+        let rules = RuleSet::default();
+        let eleven = evs(&rules, &[Rank::SIX, Rank::FIVE], Rank::SIX);
+        assert_eq!(eleven.best().0, Action::Double);
+        assert!((eleven.double.unwrap() - 0.683).abs() < 0.005, "{}", eleven);
+
+        let thirteen = evs(&rules, &[Rank::TEN, Rank::THREE], Rank::THREE);
+        assert_eq!(thirteen.best().0, Action::Stand);
+
+        let eights = evs(&rules, &[Rank::EIGHT, Rank::EIGHT], Rank::TEN);
+        assert_eq!(eights.best().0, Action::Split);
+        assert_eq!(eights.surrender, None);
+    }
+
+    #[test]
+    fn sixteen_against_ten_is_close() {
+        let rules = RuleSet {
+            surrender: SurrenderRule::Late,
+            ..RuleSet::default()
+        };
+        let sixteen = evs(&rules, &[Rank::TEN, Rank::SIX], Rank::TEN);
+        assert!((sixteen.stand - sixteen.hit).abs() < 0.01, "{}", sixteen);
+        assert!(sixteen.stand < -0.5 && sixteen.hit < -0.5);
+        assert_eq!(sixteen.best(), (Action::Surrender, -0.5));
+        assert_eq!(
+            sixteen.best_of(&[Action::Hit, Action::Stand]).0,
+            Action::Hit
+        );
+    }
+
+    #[test]
+    fn natural_pushes_a_dealer_blackjack_without_a_hole_card() {
+        // Only 95 of the 309 cards left give the dealer a blackjack too, and
+        // after a peek it is known that none of them is the hole card
+        for (no_hole_card, expected) in [(false, 1.5), (true, 1.5 * (1.0 - 95.0 / 309.0))] {
+            let rules = RuleSet {
+                no_hole_card,
+                ..RuleSet::default()
+            };
+            let natural = evs(&rules, &[Rank::ACE, Rank::TEN], Rank::ACE);
+            assert!((natural.stand - expected).abs() < 1e-12, "{}", natural);
+        }
+    }
+
+    #[test]
+    fn eights_against_ten_split_as_published() {
+        // Published for six decks, S17, DAS and splitting to four hands
+        let rules = RuleSet::default();
+        let eights = evs(&rules, &[Rank::EIGHT, Rank::EIGHT], Rank::TEN);
+        assert!((eights.split.unwrap() + 0.480).abs() < 0.01, "{}", eights);
+
+        // Being able to resplit is worth something
+        let no_resplits = RuleSet {
+            max_split_hands: 2,
+            ..RuleSet::default()
+        };
+        let once = evs(&no_resplits, &[Rank::EIGHT, Rank::EIGHT], Rank::TEN);
+        assert!(eights.split.unwrap() > once.split.unwrap() + 0.005);
+    }
+
+    #[test]
+    fn only_tens_left() {
+        // 20 against a ten with only tens to come always pushes, and hitting
+        // always busts
+        let mut shoe = Composition::new();
+        (0..8).for_each(|_| shoe.add(Rank::KING));
+        let hand = [
+            Card::new(Rank::TEN, Suit::CLUBS),
+            Card::new(Rank::JACK, Suit::CLUBS),
+        ];
+        let upcard = Card::new(Rank::QUEEN, Suit::HEARTS);
+        let evs = action_evs(&RuleSet::default(), &hand, upcard, &shoe);
+        assert_eq!(evs.stand, 0.0);
+        assert_eq!(evs.hit, -1.0);
+        assert_eq!(evs.double, Some(-2.0));
+        assert_eq!(evs.split, Some(0.0));
+    }
+}
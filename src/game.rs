@@ -0,0 +1,761 @@
+//%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+// Playing a round of blackjack
+//
+use crate::card::{Card, Rank};
+use crate::deck::CardSource;
+use crate::hand::{Hand, Player};
+use crate::money::{Money, Payout};
+use crate::rules::{RuleSet, SurrenderRule};
+use crate::strategy::Strategy;
+use std::fmt;
+
+//#############################################################################
+// What the player can choose to do with their hand
+//
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Action {
+    Hit,
+    Stand,
+    Double,
+    Split,
+    Surrender,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::Hit => write!(f, "hit"),
+            Action::Stand => write!(f, "stand"),
+            Action::Double => write!(f, "double"),
+            Action::Split => write!(f, "split"),
+            Action::Surrender => write!(f, "surrender"),
+        }
+    }
+}
+
+//#############################################################################
+// How a round finished, from the player's point of view
+//
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Outcome {
+    Win,
+    Lose,
+    Push,
+    Blackjack,
+    EvenMoney,
+    Surrendered,
+}
+
+impl Outcome {
+    /// What the player wins (or loses if negative) on a hand with this
+    /// outcome and bet, when naturals are paid at the given payout.
+    pub fn net(&self, bet: Money, blackjack_payout: Payout) -> Money {
+        match self {
+            Outcome::Win | Outcome::EvenMoney => bet,
+            Outcome::Lose => -bet,
+            Outcome::Push => Money::ZERO,
+            Outcome::Blackjack => blackjack_payout.on(bet),
+            Outcome::Surrendered => -bet.half(),
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Win => write!(f, "Player wins. Yae!"),
+            Outcome::Lose => write!(f, "Dealer wins. Boo!"),
+            Outcome::Push => write!(f, "Push. Nobody wins."),
+            Outcome::Blackjack => write!(f, "Blackjack! Player wins. Yae!"),
+            Outcome::EvenMoney => write!(f, "Blackjack paid even money. Yae!"),
+            Outcome::Surrendered => write!(f, "Surrendered. Half the bet is lost."),
+        }
+    }
+}
+
+//#############################################################################
+// A single round of blackjack between one player and the dealer
+//
+// The round is driven by calling act() until it is finished. When the dealer
+// shows an ace the player is first offered insurance (even money if they have
+// a natural), answered with take_insurance(). The dealer then peeks under an
+// ace or ten and a blackjack finishes the round straight away, as does a
+// player natural. With early surrender the peek waits until the player has
+// made their first decision, so that they can surrender before it.
+//
+// The player's hands are played one after another, a split adding a new hand
+// straight after the one being played. Once every hand is finished the dealer
+// draws according to the table's dealer rule and each hand's outcome is
+// decided. With no hole card the dealer only gets their second card then.
+//
+#[derive(Debug)]
+pub struct Round {
+    player: Player,
+    dealer: Hand,
+    rules: RuleSet,
+    bet: Money,
+    current: usize,
+    insurance_offered: bool,
+    insured: bool,
+    even_money: bool,
+    peek_pending: bool,
+    surrendered: bool,
+    outcomes: Vec<Outcome>,
+}
+
+impl Round {
+    /// Deal two cards each to the player and the dealer, alternating as at a
+    /// table, with the given bet on the player's hand. With no hole card the
+    /// dealer only gets one card.
+    pub fn deal(deck: &mut impl CardSource, rules: &RuleSet, bet: Money) -> Self {
+        let mut player = Player::new(bet);
+        let mut dealer = Hand::new();
+
+        player.hand_mut(0).add_card(deck.draw_card());
+        dealer.add_card(deck.draw_card());
+        player.hand_mut(0).add_card(deck.draw_card());
+        if !rules.no_hole_card {
+            dealer.add_card(deck.draw_card());
+        }
+
+        let mut round = Self {
+            player,
+            dealer,
+            rules: *rules,
+            bet,
+            current: 0,
+            insurance_offered: false,
+            insured: false,
+            even_money: false,
+            peek_pending: false,
+            surrendered: false,
+            outcomes: Vec::new(),
+        };
+        if round.upcard().rank == Rank::ACE {
+            round.insurance_offered = true;
+        } else {
+            round.peek(deck);
+        }
+        round
+    }
+
+    /// Is the player being asked whether they want insurance (or even money
+    /// with a natural)? No actions can be taken until it is answered.
+    pub fn insurance_offered(&self) -> bool {
+        self.insurance_offered
+    }
+
+    /// Answer the offer of insurance. Insurance costs half the bet and pays
+    /// 2:1 if the dealer has blackjack. With a natural, taking it means taking
+    /// even money: the hand is paid 1:1 straight away whatever the dealer has.
+    pub fn take_insurance(&mut self, take: bool, deck: &mut impl CardSource) {
+        if !self.insurance_offered {
+            return;
+        }
+        self.insurance_offered = false;
+        if take && self.player.hand(0).has_blackjack() {
+            self.even_money = true;
+            self.settle();
+        } else {
+            self.insured = take;
+            self.peek(deck);
+        }
+    }
+
+    /// Did the player take insurance?
+    pub fn is_insured(&self) -> bool {
+        self.insured
+    }
+
+    /// The bet the round was dealt with. Doubles and splits add to this.
+    pub fn initial_bet(&self) -> Money {
+        self.bet
+    }
+
+    /// Whether the insurance bet won, once the round is finished. None if no
+    /// insurance was taken.
+    pub fn insurance_won(&self) -> Option<bool> {
+        if self.insured && self.is_finished() {
+            Some(self.dealer.has_blackjack())
+        } else {
+            None
+        }
+    }
+
+    /// The dealer checks their hole card for blackjack, which ends the round.
+    /// A player natural also ends it, although with no hole card the dealer
+    /// still has to draw their second card to see if it is a push.
+    fn peek(&mut self, deck: &mut impl CardSource) {
+        let upcard = self.upcard().rank;
+        if self.rules.surrender == SurrenderRule::Early
+            && (upcard == Rank::ACE || upcard.blackjack_value() == 10)
+            && !self.player.hand(0).has_blackjack()
+        {
+            self.peek_pending = true;
+        } else if self.dealer.has_blackjack() {
+            self.settle();
+        } else if self.player.hand(0).has_blackjack() {
+            self.play_dealer(deck);
+        } else {
+            self.advance(deck);
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        !self.outcomes.is_empty()
+    }
+
+    /// The outcome of each of the player's hands, in the order they were
+    /// played. Empty while the round is still being played.
+    pub fn outcomes(&self) -> &[Outcome] {
+        &self.outcomes
+    }
+
+    /// What the player won or lost on each hand, settled separately and in the
+    /// same order as outcomes(). Empty while the round is still being played.
+    pub fn hand_results(&self) -> Vec<Money> {
+        self.player
+            .hands()
+            .iter()
+            .zip(&self.outcomes)
+            .map(|(hand, outcome)| outcome.net(hand.bet(), self.rules.blackjack_payout))
+            .collect()
+    }
+
+    /// What the player won or lost on insurance. The insurance bet is half the
+    /// initial bet and pays 2:1.
+    pub fn insurance_result(&self) -> Money {
+        match self.insurance_won() {
+            Some(true) => Payout::TWO_TO_ONE.on(self.bet.half()),
+            Some(false) => -self.bet.half(),
+            None => Money::ZERO,
+        }
+    }
+
+    /// What the player won or lost over the whole round, insurance included.
+    pub fn net(&self) -> Money {
+        self.hand_results().into_iter().sum::<Money>() + self.insurance_result()
+    }
+
+    /// The player and their hands.
+    pub fn player(&self) -> &Player {
+        &self.player
+    }
+
+    /// The index of the player's hand being played.
+    pub fn current_hand(&self) -> usize {
+        self.current
+    }
+
+    /// The dealer's hand. The second card is the hole card.
+    pub fn dealer(&self) -> &Hand {
+        &self.dealer
+    }
+
+    /// The dealer's face up card.
+    pub fn upcard(&self) -> Card {
+        self.dealer.cards()[0]
+    }
+
+    /// The rules the round is played under.
+    pub fn rules(&self) -> &RuleSet {
+        &self.rules
+    }
+
+    /// The actions the player may take with the hand being played. Empty once
+    /// the round is finished.
+    pub fn allowed_actions(&self) -> Vec<Action> {
+        let mut actions = Vec::new();
+        if self.is_finished() || self.insurance_offered {
+            return actions;
+        }
+        let hand = self.player.hand(self.current);
+        let one_card_only = hand.is_split_ace() && self.rules.one_card_split_aces;
+        if !one_card_only {
+            actions.push(Action::Hit);
+        }
+        actions.push(Action::Stand);
+        if !one_card_only && self.rules.can_double(hand.cards(), hand.is_from_split()) {
+            actions.push(Action::Double);
+        }
+        if self.rules.can_split(
+            hand.cards(),
+            hand.is_from_split(),
+            self.player.hands().len(),
+        ) {
+            actions.push(Action::Split);
+        }
+        // Surrender has to be the first decision on the hand that was dealt
+        let first_decision = self.player.hands().len() == 1 && hand.cards().len() == 2;
+        if first_decision && self.rules.surrender != SurrenderRule::NotAllowed {
+            actions.push(Action::Surrender);
+        }
+        actions
+    }
+
+    /// Apply the player's decision to the hand being played. Does nothing once
+    /// the round is finished, while insurance is being offered or if the action
+    /// is not one of the allowed actions.
+    pub fn act(&mut self, action: Action, deck: &mut impl CardSource) {
+        if !self.allowed_actions().contains(&action) {
+            return;
+        }
+        if self.peek_pending && action != Action::Surrender {
+            // Not surrendering early so the dealer can now check for blackjack
+            self.peek_pending = false;
+            if self.dealer.has_blackjack() {
+                self.settle();
+                return;
+            }
+        }
+        let hand = self.player.hand_mut(self.current);
+        match action {
+            Action::Hit => hand.add_card(deck.draw_card()),
+            Action::Stand => self.current += 1,
+            Action::Double => {
+                // One more card and the hand stands, bust or not
+                hand.double_bet();
+                hand.add_card(deck.draw_card());
+                self.current += 1;
+            }
+            Action::Split => self.player.split_hand(self.current),
+            Action::Surrender => {
+                self.surrendered = true;
+                self.settle();
+                return;
+            }
+        }
+        self.advance(deck);
+    }
+
+    /// Play out the rest of the round, asking the strategy about insurance and
+    /// then for every decision.
+    ///
+    /// # Panics
+    ///
+    /// If the strategy picks an action that isn't allowed.
+    pub fn play(&mut self, strategy: &(impl Strategy + ?Sized), deck: &mut impl CardSource) {
+        self.play_with(strategy, deck, |_, _| None);
+    }
+
+    /// As play(), telling the strategy the true count before each decision.
+    /// `true_count` works it out from the deck and the round so far.
+    pub fn play_counting<D: CardSource>(
+        &mut self,
+        strategy: &(impl Strategy + ?Sized),
+        deck: &mut D,
+        true_count: impl Fn(&D, &Round) -> f64,
+    ) {
+        self.play_with(strategy, deck, |deck, round| Some(true_count(deck, round)));
+    }
+
+    fn play_with<D: CardSource>(
+        &mut self,
+        strategy: &(impl Strategy + ?Sized),
+        deck: &mut D,
+        true_count: impl Fn(&D, &Round) -> Option<f64>,
+    ) {
+        if self.insurance_offered {
+            let hand = self.player.hand(0).cards();
+            let take = match true_count(deck, self) {
+                Some(count) => strategy.take_insurance_at_count(hand, &self.rules, count),
+                None => strategy.take_insurance(hand, &self.rules),
+            };
+            self.take_insurance(take, deck);
+        }
+        while !self.is_finished() {
+            let allowed = self.allowed_actions();
+            let hand = self.player.hand(self.current).cards();
+            let action = match true_count(deck, self) {
+                Some(count) => {
+                    strategy.decide_at_count(hand, self.upcard(), &allowed, &self.rules, count)
+                }
+                None => strategy.decide(hand, self.upcard(), &allowed, &self.rules),
+            };
+            assert!(
+                allowed.contains(&action),
+                "strategy chose {} which is not allowed",
+                action
+            );
+            self.act(action, deck);
+        }
+    }
+
+    /// Move play on to the next hand that needs a decision, dealing the second
+    /// card to split hands as they are reached. Once all of the hands are
+    /// finished the dealer plays.
+    fn advance(&mut self, deck: &mut impl CardSource) {
+        while self.current < self.player.hands().len() {
+            let hand = self.player.hand_mut(self.current);
+            if hand.cards().len() == 1 {
+                hand.add_card(deck.draw_card());
+            }
+            let hand = self.player.hand(self.current);
+            let finished = if hand.get_hand_value().total >= 21 {
+                true
+            } else if hand.is_split_ace() && self.rules.one_card_split_aces {
+                // Stays in play only if the ace can be resplit
+                !self.allowed_actions().contains(&Action::Split)
+            } else {
+                false
+            };
+            if !finished {
+                return;
+            }
+            self.current += 1;
+        }
+        self.play_dealer(deck);
+    }
+
+    fn play_dealer(&mut self, deck: &mut impl CardSource) {
+        if self.dealer.cards().len() == 1 {
+            self.dealer.add_card(deck.draw_card());
+        }
+        // No need to draw any more if every hand has bust or is a natural
+        let live = self
+            .player
+            .hands()
+            .iter()
+            .any(|hand| !hand.is_bust() && !hand.has_blackjack());
+        if live {
+            while self.rules.dealer.should_hit(self.dealer.get_hand_value()) {
+                self.dealer.add_card(deck.draw_card());
+            }
+        }
+        self.settle();
+    }
+
+    fn settle(&mut self) {
+        let dealer = self.dealer.get_hand_value().total;
+
+        self.outcomes = self
+            .player
+            .hands()
+            .iter()
+            .map(|hand| {
+                let player = hand.get_hand_value().total;
+                if self.surrendered {
+                    Outcome::Surrendered
+                } else if self.even_money {
+                    Outcome::EvenMoney
+                } else if hand.has_blackjack() && self.dealer.has_blackjack() {
+                    Outcome::Push
+                } else if hand.has_blackjack() {
+                    Outcome::Blackjack
+                } else if self.dealer.has_blackjack() || hand.is_bust() {
+                    Outcome::Lose
+                } else if self.dealer.is_bust() || player > dealer {
+                    Outcome::Win
+                } else if player < dealer {
+                    Outcome::Lose
+                } else {
+                    Outcome::Push
+                }
+            })
+            .collect();
+    }
+}
+
+//@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@
+// Tests
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::{Cards, Rank, Suit};
+    use crate::deck::Deck;
+    use crate::rules::{DealerRule, DoubleRule};
+
+    const BET: Money = Money::dollars(10);
+
+    /// Build a deck that deals the given ranks in order.
+    fn stacked_deck(ranks: &[Rank]) -> Deck {
+        let mut cards = Cards::new();
+        ranks
+            .iter()
+            .rev()
+            .for_each(|rank| cards.push(Card::new(*rank, Suit::SPADES)));
+        Deck::from_cards(cards)
+    }
+
+    #[test]
+    fn player_bust_loses() {
+        // Player 10+6, dealer 9+7, player hits a king
+        let mut deck = stacked_deck(&[Rank::TEN, Rank::NINE, Rank::SIX, Rank::SEVEN, Rank::KING]);
+        let mut round = Round::deal(&mut deck, &RuleSet::default(), BET);
+        round.act(Action::Hit, &mut deck);
+        assert!(round.is_finished());
+        assert_eq!(round.outcomes(), [Outcome::Lose]);
+    }
+
+    #[test]
+    fn dealer_draws_to_17_and_busts() {
+        // Player 10+8, dealer 10+6 draws a queen
+        let mut deck = stacked_deck(&[Rank::TEN, Rank::TEN, Rank::EIGHT, Rank::SIX, Rank::QUEEN]);
+        let mut round = Round::deal(&mut deck, &RuleSet::default(), BET);
+        round.act(Action::Stand, &mut deck);
+        assert_eq!(round.dealer().cards().len(), 3);
+        assert_eq!(round.outcomes(), [Outcome::Win]);
+    }
+
+    #[test]
+    fn natural_finishes_round_on_deal() {
+        let mut deck = stacked_deck(&[Rank::ACE, Rank::NINE, Rank::KING, Rank::NINE]);
+        let round = Round::deal(&mut deck, &RuleSet::default(), BET);
+        assert_eq!(round.outcomes(), [Outcome::Blackjack]);
+    }
+
+    #[test]
+    fn equal_totals_push() {
+        let mut deck = stacked_deck(&[Rank::TEN, Rank::TEN, Rank::NINE, Rank::NINE]);
+        let mut round = Round::deal(&mut deck, &RuleSet::default(), BET);
+        round.act(Action::Stand, &mut deck);
+        assert_eq!(round.outcomes(), [Outcome::Push]);
+    }
+
+    #[test]
+    fn dealer_hits_soft_17_only_under_h17() {
+        // Player 10+8, dealer A+6 then a two
+        let ranks = [Rank::TEN, Rank::ACE, Rank::EIGHT, Rank::SIX, Rank::TWO];
+
+        let mut deck = stacked_deck(&ranks);
+        let mut round = Round::deal(&mut deck, &RuleSet::default(), BET);
+        round.take_insurance(false, &mut deck);
+        round.act(Action::Stand, &mut deck);
+        assert_eq!(round.dealer().cards().len(), 2);
+        assert_eq!(round.outcomes(), [Outcome::Win]);
+
+        let rules = RuleSet {
+            dealer: DealerRule::HitSoft17,
+            ..RuleSet::default()
+        };
+        let mut deck = stacked_deck(&ranks);
+        let mut round = Round::deal(&mut deck, &rules, BET);
+        round.take_insurance(false, &mut deck);
+        round.act(Action::Stand, &mut deck);
+        assert_eq!(round.dealer().cards().len(), 3);
+        assert_eq!(round.outcomes(), [Outcome::Lose]);
+    }
+
+    #[test]
+    fn double_takes_one_card_and_doubles_bet() {
+        // Player 6+5 doubles and draws a two, dealer 10+7
+        let mut deck = stacked_deck(&[Rank::SIX, Rank::TEN, Rank::FIVE, Rank::SEVEN, Rank::TWO]);
+        let mut round = Round::deal(&mut deck, &RuleSet::default(), BET);
+        assert!(round.allowed_actions().contains(&Action::Double));
+        round.act(Action::Double, &mut deck);
+        assert_eq!(round.player().hand(0).cards().len(), 3);
+        assert_eq!(round.player().hand(0).bet(), Money::dollars(20));
+        assert_eq!(round.outcomes(), [Outcome::Lose]);
+        assert_eq!(round.net(), Money::dollars(-20));
+    }
+
+    #[test]
+    fn double_not_allowed_outside_rule() {
+        // Player 10+6 is 16, only 10 and 11 may be doubled
+        let mut deck = stacked_deck(&[Rank::TEN, Rank::TEN, Rank::SIX, Rank::SEVEN, Rank::TWO]);
+        let rules = RuleSet {
+            double: DoubleRule::TenOrEleven,
+            ..RuleSet::default()
+        };
+        let mut round = Round::deal(&mut deck, &rules, BET);
+        assert!(!round.allowed_actions().contains(&Action::Double));
+        round.act(Action::Double, &mut deck);
+        assert_eq!(round.player().hand(0).bet(), BET);
+        assert!(!round.is_finished());
+    }
+
+    #[test]
+    fn split_hands_are_played_and_settled_separately() {
+        // Player 8+8 vs dealer 10+7. First eight draws a ten and stands on 18,
+        // second eight draws a two and doubles into a ten for 20.
+        let mut deck = stacked_deck(&[
+            Rank::EIGHT,
+            Rank::TEN,
+            Rank::EIGHT,
+            Rank::SEVEN,
+            Rank::TEN,
+            Rank::TWO,
+            Rank::KING,
+        ]);
+        let mut round = Round::deal(&mut deck, &RuleSet::default(), BET);
+        round.act(Action::Split, &mut deck);
+        assert_eq!(round.player().hands().len(), 2);
+        round.act(Action::Stand, &mut deck);
+        assert_eq!(round.current_hand(), 1);
+        assert!(round.allowed_actions().contains(&Action::Double));
+        round.act(Action::Double, &mut deck);
+
+        assert_eq!(round.outcomes(), [Outcome::Win, Outcome::Win]);
+        assert_eq!(
+            round.hand_results(),
+            [Money::dollars(10), Money::dollars(20)]
+        );
+        assert_eq!(round.net(), Money::dollars(30));
+    }
+
+    #[test]
+    fn split_aces_get_one_card_each() {
+        let mut deck = stacked_deck(&[
+            Rank::ACE,
+            Rank::TEN,
+            Rank::ACE,
+            Rank::SEVEN,
+            Rank::FIVE,
+            Rank::KING,
+        ]);
+        let mut round = Round::deal(&mut deck, &RuleSet::default(), BET);
+        round.act(Action::Split, &mut deck);
+        assert!(round.is_finished());
+        assert_eq!(round.player().hand(0).cards().len(), 2);
+        assert_eq!(round.player().hand(1).cards().len(), 2);
+        // Ace and a king after a split is 21 but not a blackjack
+        assert_eq!(round.outcomes(), [Outcome::Lose, Outcome::Win]);
+    }
+
+    #[test]
+    fn split_limited_by_max_hands() {
+        let mut deck = stacked_deck(&[
+            Rank::EIGHT,
+            Rank::TEN,
+            Rank::EIGHT,
+            Rank::SEVEN,
+            Rank::EIGHT,
+            Rank::EIGHT,
+        ]);
+        let rules = RuleSet {
+            max_split_hands: 2,
+            ..RuleSet::default()
+        };
+        let mut round = Round::deal(&mut deck, &rules, BET);
+        round.act(Action::Split, &mut deck);
+        // First hand is 8+8 again but no more hands are allowed
+        assert!(!round.allowed_actions().contains(&Action::Split));
+    }
+
+    #[test]
+    fn dealer_peeks_under_ten_for_blackjack() {
+        let mut deck = stacked_deck(&[Rank::TEN, Rank::KING, Rank::NINE, Rank::ACE]);
+        let round = Round::deal(&mut deck, &RuleSet::default(), BET);
+        assert!(!round.insurance_offered());
+        assert_eq!(round.outcomes(), [Outcome::Lose]);
+    }
+
+    #[test]
+    fn insurance_pays_when_dealer_has_blackjack() {
+        let mut deck = stacked_deck(&[Rank::TEN, Rank::ACE, Rank::NINE, Rank::KING]);
+        let mut round = Round::deal(&mut deck, &RuleSet::default(), BET);
+        assert!(round.insurance_offered());
+        assert!(round.allowed_actions().is_empty());
+        round.take_insurance(true, &mut deck);
+        assert_eq!(round.outcomes(), [Outcome::Lose]);
+        assert_eq!(round.insurance_won(), Some(true));
+        assert_eq!(round.net(), Money::ZERO);
+    }
+
+    #[test]
+    fn insurance_lost_and_play_continues_without_blackjack() {
+        let mut deck = stacked_deck(&[Rank::TEN, Rank::ACE, Rank::SIX, Rank::SIX]);
+        let mut round = Round::deal(&mut deck, &RuleSet::default(), BET);
+        round.take_insurance(true, &mut deck);
+        assert!(!round.is_finished());
+        round.act(Action::Stand, &mut deck);
+        assert_eq!(round.insurance_won(), Some(false));
+    }
+
+    #[test]
+    fn even_money_for_natural_against_ace() {
+        let mut deck = stacked_deck(&[Rank::ACE, Rank::ACE, Rank::KING, Rank::KING]);
+        let mut round = Round::deal(&mut deck, &RuleSet::default(), BET);
+        assert!(round.insurance_offered());
+        round.take_insurance(true, &mut deck);
+        assert_eq!(round.outcomes(), [Outcome::EvenMoney]);
+        assert_eq!(round.insurance_won(), None);
+        assert_eq!(round.net(), BET);
+    }
+
+    #[test]
+    fn no_hole_card_blackjack_takes_doubled_bet() {
+        // Player 6+5 doubles into a two, dealer shows a ten and draws an ace
+        let mut deck = stacked_deck(&[Rank::SIX, Rank::TEN, Rank::FIVE, Rank::TWO, Rank::ACE]);
+        let rules = RuleSet {
+            no_hole_card: true,
+            ..RuleSet::default()
+        };
+        let mut round = Round::deal(&mut deck, &rules, BET);
+        assert_eq!(round.dealer().cards().len(), 1);
+        round.act(Action::Double, &mut deck);
+        assert!(round.dealer().has_blackjack());
+        assert_eq!(round.outcomes(), [Outcome::Lose]);
+        assert_eq!(round.net(), Money::dollars(-20));
+    }
+
+    #[test]
+    fn late_surrender_only_as_first_decision() {
+        let rules = RuleSet {
+            surrender: SurrenderRule::Late,
+            ..RuleSet::default()
+        };
+        let mut deck = stacked_deck(&[Rank::TEN, Rank::TEN, Rank::SIX, Rank::SEVEN, Rank::TWO]);
+        let mut round = Round::deal(&mut deck, &rules, BET);
+        assert!(round.allowed_actions().contains(&Action::Surrender));
+        round.act(Action::Hit, &mut deck);
+        assert!(!round.allowed_actions().contains(&Action::Surrender));
+
+        let mut deck = stacked_deck(&[Rank::TEN, Rank::TEN, Rank::SIX, Rank::SEVEN]);
+        let mut round = Round::deal(&mut deck, &rules, BET);
+        round.act(Action::Surrender, &mut deck);
+        assert_eq!(round.outcomes(), [Outcome::Surrendered]);
+        assert_eq!(round.net(), Money::dollars(-5));
+    }
+
+    #[test]
+    fn late_surrender_too_late_against_blackjack() {
+        let rules = RuleSet {
+            surrender: SurrenderRule::Late,
+            ..RuleSet::default()
+        };
+        let mut deck = stacked_deck(&[Rank::TEN, Rank::TEN, Rank::SIX, Rank::ACE]);
+        let round = Round::deal(&mut deck, &rules, BET);
+        assert_eq!(round.outcomes(), [Outcome::Lose]);
+    }
+
+    #[test]
+    fn early_surrender_before_peek() {
+        let rules = RuleSet {
+            surrender: SurrenderRule::Early,
+            ..RuleSet::default()
+        };
+        // Dealer has blackjack under a ten, surrendering saves half
+        let ranks = [Rank::TEN, Rank::TEN, Rank::SIX, Rank::ACE];
+        let mut deck = stacked_deck(&ranks);
+        let mut round = Round::deal(&mut deck, &rules, BET);
+        assert!(!round.is_finished());
+        round.act(Action::Surrender, &mut deck);
+        assert_eq!(round.outcomes(), [Outcome::Surrendered]);
+
+        // Not surrendering lets the dealer peek and the blackjack wins
+        let mut deck = stacked_deck(&ranks);
+        let mut round = Round::deal(&mut deck, &rules, BET);
+        round.act(Action::Hit, &mut deck);
+        assert_eq!(round.outcomes(), [Outcome::Lose]);
+        assert_eq!(round.player().hand(0).cards().len(), 2);
+    }
+
+    #[test]
+    fn blackjack_paid_at_table_payout() {
+        let ranks = [Rank::ACE, Rank::NINE, Rank::KING, Rank::NINE];
+
+        let mut deck = stacked_deck(&ranks);
+        let round = Round::deal(&mut deck, &RuleSet::default(), BET);
+        assert_eq!(round.net(), Money::dollars(15));
+
+        let rules = RuleSet {
+            blackjack_payout: Payout::SIX_TO_FIVE,
+            ..RuleSet::default()
+        };
+        let mut deck = stacked_deck(&ranks);
+        let round = Round::deal(&mut deck, &rules, Money::cents(501));
+        assert_eq!(round.net(), Money::cents(501).times(6, 5));
+    }
+}
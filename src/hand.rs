@@ -0,0 +1,283 @@
+//%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+// A player's hand and its blackjack value
+//
+use crate::card::{Card, Cards, Rank};
+use crate::money::Money;
+use std::fmt;
+
+//#############################################################################
+// The blackjack value of a hand
+//
+// Aces count as 11 unless that would bust the hand, in which case they count
+// as 1. A hand is soft when an ace is still being counted as 11. A blackjack
+// (natural) is 21 with the first two cards, which beats a 21 made with more.
+//
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct HandValue {
+    pub total: u8,
+    pub soft: bool,
+    pub blackjack: bool,
+}
+
+impl HandValue {
+    /// Work out the value of a bunch of cards.
+    pub fn of(cards: &[Card]) -> Self {
+        let mut value = 0;
+        let mut aces = 0;
+
+        cards.iter().for_each(|card| {
+            let mut card_value = card.rank.blackjack_value();
+            if card_value == 1 {
+                aces += 1;
+                card_value = 11;
+            }
+            value += card_value;
+        });
+
+        while value > 21 && aces > 0 {
+            value -= 10;
+            aces -= 1;
+        }
+        Self {
+            total: value,
+            soft: aces > 0,
+            blackjack: cards.len() == 2 && value == 21,
+        }
+    }
+}
+
+impl fmt::Display for HandValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.blackjack {
+            write!(f, "blackjack")
+        } else if self.soft {
+            write!(f, "soft {}", self.total)
+        } else {
+            write!(f, "{}", self.total)
+        }
+    }
+}
+
+//#############################################################################
+// A single hand of cards and the bet riding on it
+//
+// The dealer's hand has no bet.
+//
+#[derive(Clone, Debug)]
+pub struct Hand {
+    cards: Cards,
+    bet: Money,
+    doubled: bool,
+    from_split: bool,
+}
+
+impl Hand {
+    /// An empty hand with no bet on it.
+    pub fn new() -> Self {
+        Self::with_bet(Money::ZERO)
+    }
+
+    /// An empty hand with a bet on it.
+    pub fn with_bet(bet: Money) -> Self {
+        Self {
+            cards: Cards::new(),
+            bet,
+            doubled: false,
+            from_split: false,
+        }
+    }
+
+    /// The cards in the hand.
+    pub fn cards(&self) -> &Cards {
+        &self.cards
+    }
+
+    pub fn add_card(self: &mut Hand, card: Option<Card>) {
+        self.cards.push(card.unwrap())
+    }
+
+    /// The value of the hand. A split hand that makes 21 with two cards is not
+    /// a blackjack.
+    pub fn get_hand_value(self: &Hand) -> HandValue {
+        let mut value = HandValue::of(&self.cards);
+        value.blackjack &= !self.from_split;
+        value
+    }
+
+    pub fn has_blackjack(self: &Hand) -> bool {
+        self.get_hand_value().blackjack
+    }
+
+    pub fn is_bust(self: &Hand) -> bool {
+        self.get_hand_value().total > 21
+    }
+
+    /// The money riding on the hand.
+    pub fn bet(&self) -> Money {
+        self.bet
+    }
+
+    /// Has the hand been doubled down?
+    pub fn is_doubled(&self) -> bool {
+        self.doubled
+    }
+
+    /// Double the bet. The caller deals the one extra card.
+    pub fn double_bet(&mut self) {
+        self.bet += self.bet;
+        self.doubled = true;
+    }
+
+    /// Did the hand come from splitting a pair?
+    pub fn is_from_split(&self) -> bool {
+        self.from_split
+    }
+
+    /// Is the hand an ace that was split off a pair of aces?
+    pub fn is_split_ace(&self) -> bool {
+        self.from_split && self.cards.first().map(|card| card.rank) == Some(Rank::ACE)
+    }
+}
+
+impl Default for Hand {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//#############################################################################
+// A player who can hold several hands after splitting pairs
+//
+#[derive(Debug)]
+pub struct Player {
+    hands: Vec<Hand>,
+}
+
+impl Player {
+    /// A player with a single empty hand with the given bet on it.
+    pub fn new(bet: Money) -> Self {
+        Self {
+            hands: vec![Hand::with_bet(bet)],
+        }
+    }
+
+    pub fn hands(&self) -> &[Hand] {
+        &self.hands
+    }
+
+    pub fn hand(&self, index: usize) -> &Hand {
+        &self.hands[index]
+    }
+
+    pub fn hand_mut(&mut self, index: usize) -> &mut Hand {
+        &mut self.hands[index]
+    }
+
+    /// Split the hand at `index` into two. The second card moves into a new
+    /// hand, with the same bet, that is played straight after the first.
+    /// Each hand is left with one card to be dealt to.
+    pub fn split_hand(&mut self, index: usize) {
+        let hand = &mut self.hands[index];
+        let card = hand.cards.pop();
+        hand.from_split = true;
+
+        let mut new_hand = Hand::with_bet(hand.bet);
+        new_hand.from_split = true;
+        new_hand.add_card(card);
+        self.hands.insert(index + 1, new_hand);
+    }
+}
+
+//@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@
+// Tests
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::Suit;
+
+    #[test]
+    fn adding_card_to_players_hand() {
+        let mut hand = Hand::new();
+        let card = Card::new(Rank::ACE, Suit::DIAMONDS);
+        hand.add_card(std::option::Option::Some(card));
+        assert_eq!(hand.get_hand_value().total, 11);
+    }
+
+    #[test]
+    fn splitting_makes_two_hands_with_same_bet() {
+        let mut player = Player::new(Money::dollars(5));
+        player.hand_mut(0).double_bet();
+        player
+            .hand_mut(0)
+            .add_card(Some(Card::new(Rank::EIGHT, Suit::HEARTS)));
+        player
+            .hand_mut(0)
+            .add_card(Some(Card::new(Rank::EIGHT, Suit::CLUBS)));
+        player.split_hand(0);
+
+        assert_eq!(player.hands().len(), 2);
+        for hand in player.hands() {
+            assert_eq!(hand.cards().len(), 1);
+            assert_eq!(hand.bet(), Money::dollars(10));
+            assert!(hand.is_from_split());
+        }
+    }
+
+    #[test]
+    fn split_twenty_one_is_not_blackjack() {
+        let mut player = Player::new(Money::dollars(5));
+        player
+            .hand_mut(0)
+            .add_card(Some(Card::new(Rank::ACE, Suit::HEARTS)));
+        player
+            .hand_mut(0)
+            .add_card(Some(Card::new(Rank::ACE, Suit::CLUBS)));
+        player.split_hand(0);
+        player
+            .hand_mut(0)
+            .add_card(Some(Card::new(Rank::KING, Suit::CLUBS)));
+        assert_eq!(player.hand(0).get_hand_value().total, 21);
+        assert!(!player.hand(0).has_blackjack());
+        assert!(player.hand(0).is_split_ace());
+    }
+
+    #[test]
+    fn ace_counted_as_eleven_is_soft() {
+        let cards = [
+            Card::new(Rank::ACE, Suit::HEARTS),
+            Card::new(Rank::SIX, Suit::CLUBS),
+        ];
+        let value = HandValue::of(&cards);
+        assert_eq!(value.total, 17);
+        assert!(value.soft);
+    }
+
+    #[test]
+    fn ace_counted_as_one_is_hard() {
+        let cards = [
+            Card::new(Rank::ACE, Suit::HEARTS),
+            Card::new(Rank::SIX, Suit::CLUBS),
+            Card::new(Rank::KING, Suit::CLUBS),
+        ];
+        let value = HandValue::of(&cards);
+        assert_eq!(value.total, 17);
+        assert!(!value.soft);
+    }
+
+    #[test]
+    fn natural_is_blackjack_but_three_card_21_is_not() {
+        let natural = [
+            Card::new(Rank::ACE, Suit::HEARTS),
+            Card::new(Rank::QUEEN, Suit::CLUBS),
+        ];
+        let three_cards = [
+            Card::new(Rank::SEVEN, Suit::HEARTS),
+            Card::new(Rank::SEVEN, Suit::CLUBS),
+            Card::new(Rank::SEVEN, Suit::SPADES),
+        ];
+        assert!(HandValue::of(&natural).blackjack);
+        assert_eq!(HandValue::of(&three_cards).total, 21);
+        assert!(!HandValue::of(&three_cards).blackjack);
+    }
+}
@@ -0,0 +1,453 @@
+//%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+// Working out the true count indices of playing deviations
+//
+use crate::card::{Card, Cards, Rank, Suit};
+use crate::chart::{Chart, ChartAction};
+use crate::composition::Composition;
+use crate::counting::CountingSystem;
+use crate::deviations::{Deviation, DeviationAction, DeviationHand, DeviationTable, Threshold};
+use crate::ev::{ActionEvs, EvCalculator};
+use crate::game::Action;
+use crate::rules::RuleSet;
+use crate::strategy::{BasicStrategy, Strategy};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+//#############################################################################
+// Finds the true counts at which basic strategy stops being the best play
+//
+// For each whole true count the shoe is given the composition that count
+// stands for: the cards left are taken to be decks_remaining decks, with
+// each rank made scarcer or more plentiful in proportion to its tag until
+// the tags left add up to the count. The exact expected values of a hand's
+// actions are worked out at each count, and the index is where the best
+// action departs from basic strategy, found by interpolating between the
+// counts either side and rounding to a whole number. An index that rounds
+// to max_count or beyond is dropped, as the crossing wasn't seen.
+//
+// Hands are worked out from a representative two card hand, such as 10-6
+// for a hard 16, so the indices suit totals rather than particular cards.
+//
+#[derive(Clone, Debug)]
+pub struct IndexGenerator {
+    rules: RuleSet,
+    counting: CountingSystem,
+    /// The decks left in the shoe, half of them by default.
+    pub decks_remaining: f64,
+    /// The highest true count tried either side of zero.
+    pub max_count: i32,
+    pub threads: usize,
+}
+
+impl IndexGenerator {
+    pub fn new(rules: &RuleSet, counting: &CountingSystem) -> Self {
+        Self {
+            rules: *rules,
+            counting: counting.clone(),
+            decks_remaining: (rules.decks as f64 / 2.0).max(1.0),
+            max_count: 10,
+            threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+        }
+    }
+
+    /// The cards left in the shoe at a true count.
+    pub fn composition_at(&self, true_count: f64) -> Composition {
+        let ideal = self.ideal_counts(true_count);
+
+        // Ranks with the same tag are rounded together, so that rounding
+        // doesn't throw the count off, and then shared out evenly
+        let mut groups: Vec<(f64, Vec<usize>)> = Vec::new();
+        for (index, rank) in Rank::iterator().enumerate() {
+            let tag = self.counting.tag(*rank);
+            match groups.iter_mut().find(|(group_tag, _)| *group_tag == tag) {
+                Some((_, ranks)) => ranks.push(index),
+                None => groups.push((tag, vec![index])),
+            }
+        }
+        let group_ideal: Vec<f64> = groups
+            .iter()
+            .map(|(_, ranks)| ranks.iter().map(|index| ideal[*index]).sum())
+            .collect();
+        let group_most: Vec<u32> = groups
+            .iter()
+            .map(|(_, ranks)| ranks.len() as u32 * 4 * self.rules.decks as u32)
+            .collect();
+        let mut counts = [0; 13];
+        for ((_, ranks), total) in groups
+            .iter()
+            .zip(round_keeping_total(&group_ideal, &group_most))
+        {
+            for (which, index) in ranks.iter().enumerate() {
+                let share = total / ranks.len() as u32;
+                counts[*index] = share + u32::from((which as u32) < total % ranks.len() as u32);
+            }
+        }
+
+        let mut composition = Composition::new();
+        for (rank, count) in Rank::iterator().zip(counts) {
+            (0..count).for_each(|_| composition.add(*rank));
+        }
+        composition
+    }
+
+    /// The number of cards of each rank at a true count, before rounding.
+    fn ideal_counts(&self, true_count: f64) -> Vec<f64> {
+        let squares: f64 = Rank::iterator()
+            .map(|rank| self.counting.tag(*rank).powi(2))
+            .sum();
+        let shift = if squares > 0.0 {
+            true_count / (4.0 * squares)
+        } else {
+            0.0
+        };
+        let most = 4.0 * self.rules.decks as f64;
+        Rank::iterator()
+            .map(|rank| {
+                (4.0 * self.decks_remaining * (1.0 - shift * self.counting.tag(*rank)))
+                    .clamp(0.0, most)
+            })
+            .collect()
+    }
+
+    /// The deviations from basic strategy for one hand against an upcard,
+    /// 2 to 11 for an ace, or for insurance.
+    pub fn deviations_for(&self, hand: DeviationHand, upcard: u8) -> Vec<Deviation> {
+        if hand == DeviationHand::Insurance {
+            return self.insurance().into_iter().collect();
+        }
+        let mut calculator = EvCalculator::new(&self.rules);
+        let evs: Vec<ActionEvs> = self
+            .counts()
+            .map(|true_count| {
+                let shoe = self.composition_at(true_count as f64);
+                action_evs(&mut calculator, &shoe, hand, upcard)
+            })
+            .collect();
+        self.deviations_from(hand, upcard, &evs)
+    }
+
+    /// The deviations for insurance and every hand the chart covers against
+    /// every upcard: pairs first, then hard and soft totals.
+    pub fn generate(&self) -> DeviationTable {
+        let mut hands = Vec::new();
+        for upcard in 2..=11 {
+            hands.extend(Chart::PAIR_VALUES.map(|card| (DeviationHand::Pair(card), upcard)));
+        }
+        for upcard in 2..=11 {
+            hands.extend((8..=17).map(|total| (DeviationHand::Hard(total), upcard)));
+        }
+        for upcard in 2..=11 {
+            hands.extend((13..=20).map(|total| (DeviationHand::Soft(total), upcard)));
+        }
+
+        // Each true count is a shoe of its own, so they're shared out
+        // between the threads
+        let counts: Vec<i32> = self.counts().collect();
+        let next_count = AtomicUsize::new(0);
+        let results = Mutex::new(vec![Vec::new(); counts.len()]);
+        thread::scope(|scope| {
+            for _ in 0..self.threads.clamp(1, counts.len()) {
+                scope.spawn(|| loop {
+                    let index = next_count.fetch_add(1, Ordering::Relaxed);
+                    let Some(true_count) = counts.get(index) else {
+                        break;
+                    };
+                    let shoe = self.composition_at(*true_count as f64);
+                    let mut calculator = EvCalculator::new(&self.rules);
+                    let evs: Vec<ActionEvs> = hands
+                        .iter()
+                        .map(|(hand, upcard)| action_evs(&mut calculator, &shoe, *hand, *upcard))
+                        .collect();
+                    results.lock().unwrap()[index] = evs;
+                });
+            }
+        });
+        let results = results.into_inner().unwrap();
+
+        let mut deviations = self.insurance().into_iter().collect::<Vec<_>>();
+        for (which, (hand, upcard)) in hands.iter().enumerate() {
+            let evs: Vec<ActionEvs> = results.iter().map(|evs| evs[which]).collect();
+            deviations.extend(self.deviations_from(*hand, *upcard, &evs));
+        }
+        DeviationTable::new(deviations)
+    }
+
+    fn counts(&self) -> impl Iterator<Item = i32> {
+        -self.max_count..=self.max_count
+    }
+
+    /// Take insurance once more than a third of the cards left are tens.
+    /// That only depends on the share of tens, so it's worked out before
+    /// rounding.
+    fn insurance(&self) -> Option<Deviation> {
+        let gain = |true_count: i32| {
+            let ideal = self.ideal_counts(true_count as f64);
+            let tens: f64 = ideal[9..].iter().sum();
+            3.0 * tens / ideal.iter().sum::<f64>() - 1.0
+        };
+        let gains: Vec<f64> = self.counts().map(gain).collect();
+        let index = self.index_above(&gains)?;
+        Some(Deviation {
+            hand: DeviationHand::Insurance,
+            upcard: 11,
+            threshold: Threshold::AtOrAbove(index),
+            action: DeviationAction::Insure(true),
+        })
+    }
+
+    /// The deviations shown by the expected values at each true count.
+    fn deviations_from(
+        &self,
+        hand: DeviationHand,
+        upcard: u8,
+        evs: &[ActionEvs],
+    ) -> Vec<Deviation> {
+        let zero = self.max_count as usize;
+        let cards = Cards(representative(hand));
+        let allowed: Vec<Action> = [
+            Action::Hit,
+            Action::Stand,
+            Action::Double,
+            Action::Split,
+            Action::Surrender,
+        ]
+        .into_iter()
+        .filter(|action| evs[zero].get(*action).is_some())
+        .collect();
+        let base =
+            BasicStrategy::new(&self.rules).decide(&cards, card_of(upcard), &allowed, &self.rules);
+        let best: Vec<Action> = evs.iter().map(|evs| evs.best_of(&allowed).0).collect();
+        let gains = |alternative: Action| -> Vec<f64> {
+            let ev = |evs: &ActionEvs, action| evs.get(action).expect("allowed at every count");
+            evs.iter()
+                .map(|evs| ev(evs, alternative) - ev(evs, base))
+                .collect()
+        };
+
+        // Whatever is best at the first count either side of zero where
+        // basic strategy isn't
+        let mut alternatives: Vec<Action> = Vec::new();
+        let above = best[zero..].iter().find(|action| **action != base);
+        let below = best[..zero].iter().rev().find(|action| **action != base);
+        for alternative in above.into_iter().chain(below) {
+            if !alternatives.contains(alternative) {
+                alternatives.push(*alternative);
+            }
+        }
+
+        let is_pair = matches!(hand, DeviationHand::Pair(_));
+        let mut deviations = Vec::new();
+        for alternative in alternatives {
+            // Pairs that aren't split either way are played as their total
+            if is_pair && base != Action::Split && alternative != Action::Split {
+                continue;
+            }
+            let gains = gains(alternative);
+            let better_high = gains[gains.len() - 1] > 0.0;
+            let better_low = gains[0] > 0.0;
+            let deviation = |threshold, action| Deviation {
+                hand,
+                upcard,
+                threshold,
+                action: DeviationAction::Play(action),
+            };
+            if base == Action::Surrender {
+                // Surrender above the index and play the alternative below
+                // it, as a surrender deviation always does
+                let code = match alternative {
+                    Action::Hit => ChartAction::SurrenderOrHit,
+                    Action::Stand => ChartAction::SurrenderOrStand,
+                    Action::Split => ChartAction::SurrenderOrSplit,
+                    _ => continue,
+                };
+                let losses: Vec<f64> = gains.iter().map(|gain| -gain).collect();
+                if let (true, Some(index)) = (better_low, self.index_above(&losses)) {
+                    deviations.push(deviation(Threshold::AtOrAbove(index), code));
+                }
+                continue;
+            }
+            let code = match alternative {
+                Action::Hit => ChartAction::Hit,
+                Action::Stand => ChartAction::Stand,
+                Action::Double if base == Action::Stand => ChartAction::DoubleOrStand,
+                Action::Double => ChartAction::DoubleOrHit,
+                Action::Split => ChartAction::Split,
+                Action::Surrender if base == Action::Stand => ChartAction::SurrenderOrStand,
+                Action::Surrender => ChartAction::SurrenderOrHit,
+            };
+            if let (true, Some(index)) = (better_high, self.index_above(&gains)) {
+                deviations.push(deviation(Threshold::AtOrAbove(index), code));
+            }
+            if let (true, Some(index)) = (better_low, self.index_below(&gains)) {
+                deviations.push(deviation(Threshold::Below(index), code));
+            }
+        }
+        deviations
+    }
+
+    /// Where gains that rise with the count turn positive for good, rounded
+    /// to a whole true count, or None if they don't within the counts tried.
+    fn index_above(&self, gains: &[f64]) -> Option<f64> {
+        match gains.iter().rposition(|gain| *gain <= 0.0) {
+            Some(last) if last + 1 < gains.len() => self.crossing(gains, last),
+            _ => None,
+        }
+    }
+
+    /// Where gains that fall with the count stop being positive, or None if
+    /// they don't within the counts tried.
+    fn index_below(&self, gains: &[f64]) -> Option<f64> {
+        match gains.iter().position(|gain| *gain <= 0.0) {
+            None | Some(0) => None,
+            Some(first) => self.crossing(gains, first - 1),
+        }
+    }
+
+    /// The true count where the gain changes sign between two whole counts,
+    /// given the position of the lower one, unless it rounds to the edge of
+    /// the counts tried.
+    fn crossing(&self, gains: &[f64], low: usize) -> Option<f64> {
+        let between = gains[low] / (gains[low] - gains[low + 1]);
+        // Adding zero turns a rounded -0 into 0
+        let index = (low as f64 + between - self.max_count as f64).round() + 0.0;
+        (index.abs() < self.max_count as f64).then_some(index)
+    }
+}
+
+/// Round each number, keeping the total and no more than the most of each:
+/// round down, then hand out what's left to the largest remainders.
+fn round_keeping_total(ideal: &[f64], most: &[u32]) -> Vec<u32> {
+    let mut counts: Vec<u32> = ideal.iter().map(|count| count.floor() as u32).collect();
+    let mut left = ideal.iter().sum::<f64>().round() as i64 - counts.iter().sum::<u32>() as i64;
+    let mut by_remainder: Vec<usize> = (0..ideal.len()).collect();
+    by_remainder.sort_by(|a, b| {
+        let remainder = |index: &usize| ideal[*index] - ideal[*index].floor();
+        remainder(b).total_cmp(&remainder(a))
+    });
+    for index in by_remainder {
+        if left <= 0 {
+            break;
+        }
+        if counts[index] < most[index] {
+            counts[index] += 1;
+            left -= 1;
+        }
+    }
+    counts
+}
+
+/// The expected values for the hand's representative cards. The shoe is the
+/// cards not yet seen, so they aren't taken out of it again.
+fn action_evs(
+    calculator: &mut EvCalculator,
+    shoe: &Composition,
+    hand: DeviationHand,
+    upcard: u8,
+) -> ActionEvs {
+    calculator.action_evs(&representative(hand), card_of(upcard), shoe)
+}
+
+/// Two cards making up the hand, avoiding pairs for totals.
+fn representative(hand: DeviationHand) -> Vec<Card> {
+    let values = match hand {
+        DeviationHand::Hard(total @ 12..) => [10, total - 10],
+        DeviationHand::Hard(total) => [6.min(total - 2), total - 6.min(total - 2)],
+        DeviationHand::Soft(total) => [11, total - 11],
+        DeviationHand::Pair(card) => [card, card],
+        DeviationHand::Insurance => return Vec::new(),
+    };
+    values.into_iter().map(card_of).collect()
+}
+
+fn card_of(value: u8) -> Card {
+    let rank = match value {
+        11 => Rank::ACE,
+        value => *Rank::iterator()
+            .nth(value as usize - 1)
+            .expect("value is from 2 to 11"),
+    };
+    Card::new(rank, Suit::SPADES)
+}
+
+//@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@
+// Tests
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compositions_have_the_true_count() {
+        let rules = RuleSet::default();
+        let counting = CountingSystem::hi_lo();
+        let generator = IndexGenerator::new(&rules, &counting);
+        for true_count in [-4.0, 0.0, 2.0, 5.0] {
+            let shoe = generator.composition_at(true_count);
+            let tags: f64 = Rank::iterator()
+                .map(|rank| counting.tag(*rank) * shoe.count(*rank) as f64)
+                .sum();
+            let decks = shoe.total() as f64 / 52.0;
+            assert_eq!(shoe.total(), 156);
+            assert!((-tags / decks - true_count).abs() < 0.35, "{}", true_count);
+        }
+    }
+
+    #[test]
+    fn hi_lo_indices_in_a_shoe() {
+        let rules = RuleSet::default();
+        let generator = IndexGenerator::new(&rules, &CountingSystem::hi_lo());
+        let index = |hand, upcard| {
+            generator
+                .deviations_for(hand, upcard)
+                .iter()
+                .map(|deviation| (deviation.threshold, deviation.action))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            index(DeviationHand::Insurance, 11),
+            vec![(Threshold::AtOrAbove(3.0), DeviationAction::Insure(true))]
+        );
+        assert_eq!(
+            index(DeviationHand::Hard(15), 10),
+            vec![(
+                Threshold::AtOrAbove(4.0),
+                DeviationAction::Play(ChartAction::Stand)
+            )]
+        );
+        assert_eq!(
+            index(DeviationHand::Hard(12), 4),
+            vec![(
+                Threshold::Below(0.0),
+                DeviationAction::Play(ChartAction::Hit)
+            )]
+        );
+    }
+
+    #[test]
+    fn indices_stay_inside_the_counts_tried() {
+        let rules = RuleSet::default();
+        let generator = IndexGenerator {
+            max_count: 4,
+            ..IndexGenerator::new(&rules, &CountingSystem::hi_lo())
+        };
+        let table = generator.generate();
+        assert!(!table.deviations().is_empty());
+        for deviation in table.deviations() {
+            let index = match deviation.threshold {
+                Threshold::AtOrAbove(index) | Threshold::Below(index) => index,
+            };
+            assert!(index.abs() < 4.0, "{}", deviation);
+        }
+
+        // Gains from -4 to +4 that cross inside, never cross, or cross so
+        // close to the edge that the index rounds to it
+        let inside = [-1.0, -0.1, 0.9, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+        assert_eq!(generator.index_above(&inside), Some(-3.0));
+        assert_eq!(generator.index_above(&[1.0; 9]), None);
+        let edge = [-8.0, -7.0, -6.0, -5.0, -4.0, -3.0, -2.0, -1.0, 0.5];
+        assert_eq!(generator.index_above(&edge), None);
+        let falling: Vec<f64> = edge.iter().rev().copied().collect();
+        assert_eq!(generator.index_below(&falling), None);
+    }
+}
@@ -0,0 +1,55 @@
+//%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+// Rust implementation of a simple blackjack simulator. Based on C++ code
+// written by Dave Plummer
+// See https://github.com/davepl/blackjack/blob/main/blackjack.cpp
+//
+// The library holds the cards, deck, hand and game logic so that it can be
+// reused by the blackjack binary and by other tools.
+//
+#![allow(non_camel_case_types)]
+#![allow(clippy::upper_case_acronyms)]
+
+pub mod betting;
+pub mod card;
+pub mod cd_strategy;
+pub mod chart;
+pub mod composition;
+pub mod counting;
+pub mod dealer;
+pub mod deck;
+pub mod deviations;
+pub mod ev;
+pub mod game;
+pub mod hand;
+pub mod indices;
+pub mod money;
+pub mod risk;
+pub mod rules;
+pub mod shoe;
+pub mod simulation;
+pub mod strategy;
+
+pub use betting::{BetRamp, RampError};
+pub use card::{Card, Cards, Rank, Suit};
+pub use cd_strategy::{CompositionStrategy, Difference};
+pub use chart::{Chart, ChartAction, ChartError};
+pub use composition::Composition;
+pub use counting::{Counter, CountingError, CountingSystem};
+pub use dealer::{dealer_probabilities, DealerProbabilities};
+pub use deck::{CardSource, Deck};
+pub use deviations::{
+    Deviation, DeviationAction, DeviationHand, DeviationStrategy, DeviationTable, Threshold,
+};
+pub use ev::{action_evs, ActionEvs, EvCalculator};
+pub use game::{Action, Outcome, Round};
+pub use hand::{Hand, HandValue, Player};
+pub use indices::IndexGenerator;
+pub use money::{Money, Payout};
+pub use risk::WinRate;
+pub use rules::{DealerRule, DoubleRule, RuleSet, RulesError, SurrenderRule};
+pub use shoe::Shoe;
+pub use simulation::{
+    deviation_gains, simulate, simulate_parallel, simulate_trips, simulate_with_progress,
+    DeviationGain, SimulationConfig, SimulationStats, TripStats,
+};
+pub use strategy::{BasicStrategy, ChartStrategy, Strategy};
@@ -1,388 +1,906 @@
 //%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
-// Rust implementation of a simple blackjack simulator. Based on C++ code
-// written by Dave Plummer
-// See https://github.com/davepl/blackjack/blob/main/blackjack.cpp
+// Play a round of blackjack at the terminal, simulate a lot of rounds with
+// `blackjack simulate`, work out deviation indices with `blackjack indices`
+// or size a bankroll with `blackjack risk`. All of the game logic lives in the
+// blackjack library.
 //
-#![allow(non_camel_case_types)]
-#![allow(clippy::upper_case_acronyms)]
-use rand::prelude::SliceRandom;
-use rand::thread_rng;
-use std::fmt;
-use std::slice::Iter;
+use blackjack::{
+    deviation_gains, simulate_parallel, simulate_trips, simulate_with_progress, Action,
+    BasicStrategy, BetRamp, Cards, ChartError, ChartStrategy, CompositionStrategy, CountingSystem,
+    DeviationStrategy, DeviationTable, IndexGenerator, Money, Round, RuleSet, SimulationConfig,
+    SimulationStats, Strategy, WinRate,
+};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::io::{self, Write};
+use std::process;
+use std::time::Instant;
+
+const USAGE: &str = "Usage: blackjack [--seed <number>] [--bet <dollars>] [--auto]
+                 [--rules <preset or file.toml>] [--save-rules <file.toml>]
+                 [--chart <file.csv>] [--save-chart <file.csv>]
+                 [--save-cd-chart <file.csv>]
+       blackjack simulate [--hands <number>] [--rules <preset or file.toml>]
+                 [--strategy basic|cd|<file.csv>] [--seed <number>]
+                 [--threads <number>] [--precision <percent>]
+                 [--log-every <number>] [--count <system or file.toml>]
+                 [--ramp <file.toml>] [--unit <dollars>] [--rounds-per-hour <number>]
+                 [--deviations <table or file.csv>[,...]] [--deviation-gains]
+       blackjack indices [--rules <preset or file.toml>]
+                 [--count <system or file.toml>] [--threads <number>]
+                 [--save <file.csv>]
+       blackjack risk --bankroll <dollars> [--ev <units> --sd <units>]
+                 [--risk <percent>] [--trip-rounds <number>] [--trips <number>]
+                 [any simulate option]
+Presets: vegas-strip, downtown-vegas, atlantic-city, european, single-deck-6-5
+Counts: hi-lo, ko, hi-opt-1, hi-opt-2, omega-2, zen, wong-halves
+Deviations: illustrious-18, fab-4";
 
 //#############################################################################
-// Card's rank (numeric value)
+// Command line options
 //
-#[derive(Copy, Clone, Debug, PartialEq)]
-enum Rank {
-    ACE,
-    TWO,
-    THREE,
-    FOUR,
-    FIVE,
-    SIX,
-    SEVEN,
-    EIGHT,
-    NINE,
-    TEN,
-    JACK,
-    QUEEN,
-    KING,
+#[derive(Debug)]
+struct Options {
+    seed: Option<u64>,
+    bet: Money,
+    rules: RuleSet,
+    save_rules: Option<String>,
+    auto: bool,
+    chart: Option<String>,
+    save_chart: Option<String>,
+    save_cd_chart: Option<String>,
 }
 
-impl Rank {
-    /// Return the numeric value of the card's rank. Ace is 1 and
-    /// Jack is 11, Queen is 12, and King is 13.
-    fn as_number(&self) -> u8 {
-        match self {
-            Rank::ACE => 1,
-            Rank::TWO => 2,
-            Rank::THREE => 3,
-            Rank::FOUR => 4,
-            Rank::FIVE => 5,
-            Rank::SIX => 6,
-            Rank::SEVEN => 7,
-            Rank::EIGHT => 8,
-            Rank::NINE => 9,
-            Rank::TEN => 10,
-            Rank::JACK => 11,
-            Rank::QUEEN => 12,
-            Rank::KING => 13,
-        }
-    }
-
-    /// Return the rank of the card as a string. For display purposes.
-    fn as_string(&self) -> String {
-        match self {
-            Rank::ACE => "ACE".to_string(),
-            Rank::TWO => "TWO".to_string(),
-            Rank::THREE => "THREE".to_string(),
-            Rank::FOUR => "FOUR".to_string(),
-            Rank::FIVE => "FIVE".to_string(),
-            Rank::SIX => "SIX".to_string(),
-            Rank::SEVEN => "SEVEN".to_string(),
-            Rank::EIGHT => "EIGHT".to_string(),
-            Rank::NINE => "NINE".to_string(),
-            Rank::TEN => "TEN".to_string(),
-            Rank::JACK => "JACK".to_string(),
-            Rank::QUEEN => "QUEEN".to_string(),
-            Rank::KING => "KING".to_string(),
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            seed: None,
+            bet: Money::dollars(10),
+            rules: RuleSet::default(),
+            save_rules: None,
+            auto: false,
+            chart: None,
+            save_chart: None,
+            save_cd_chart: None,
         }
     }
+}
 
-    /// Return the rank of the card as a string. For display purposes.
-    fn as_character(&self) -> String {
-        match self {
-            Rank::ACE => "A".to_string(),
-            Rank::TWO => "2".to_string(),
-            Rank::THREE => "3".to_string(),
-            Rank::FOUR => "4".to_string(),
-            Rank::FIVE => "5".to_string(),
-            Rank::SIX => "6".to_string(),
-            Rank::SEVEN => "7".to_string(),
-            Rank::EIGHT => "8".to_string(),
-            Rank::NINE => "9".to_string(),
-            Rank::TEN => "0".to_string(),
-            Rank::JACK => "J".to_string(),
-            Rank::QUEEN => "Q".to_string(),
-            Rank::KING => "K".to_string(),
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options::default();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--seed" => {
+                    let value = args.next().ok_or("--seed needs a value")?;
+                    let seed = value
+                        .parse()
+                        .map_err(|_| format!("invalid --seed value '{}'", value))?;
+                    options.seed = Some(seed);
+                }
+                "--bet" => {
+                    let value = args.next().ok_or("--bet needs a value")?;
+                    let bet: Money = value.parse()?;
+                    if bet <= Money::ZERO {
+                        return Err(format!("--bet must be more than zero, not {}", bet));
+                    }
+                    options.bet = bet;
+                }
+                "--rules" => {
+                    let value = args.next().ok_or("--rules needs a value")?;
+                    options.rules = parse_rules(&value)?;
+                }
+                "--save-rules" => {
+                    let value = args.next().ok_or("--save-rules needs a value")?;
+                    options.save_rules = Some(value);
+                }
+                "--auto" => options.auto = true,
+                "--chart" => {
+                    let value = args.next().ok_or("--chart needs a value")?;
+                    options.chart = Some(value);
+                    options.auto = true;
+                }
+                "--save-chart" => {
+                    let value = args.next().ok_or("--save-chart needs a value")?;
+                    options.save_chart = Some(value);
+                }
+                "--save-cd-chart" => {
+                    let value = args.next().ok_or("--save-cd-chart needs a value")?;
+                    options.save_cd_chart = Some(value);
+                }
+                "-h" | "--help" => {
+                    println!("{}", USAGE);
+                    process::exit(0);
+                }
+                _ => return Err(format!("unknown argument '{}'", arg)),
+            }
         }
-    }
-
-    /// Allow rank to be used in an iterator. Iteration will progress from ACE to KING.
-    /// Note: Trick is to have a static array and iterate through that.
-    fn iterator() -> Iter<'static, Rank> {
-        static RANK: [Rank; 13] = [
-            Rank::ACE,
-            Rank::TWO,
-            Rank::THREE,
-            Rank::FOUR,
-            Rank::FIVE,
-            Rank::SIX,
-            Rank::SEVEN,
-            Rank::EIGHT,
-            Rank::NINE,
-            Rank::TEN,
-            Rank::JACK,
-            Rank::QUEEN,
-            Rank::KING,
-        ];
-        RANK.iter()
+        Ok(options)
     }
 }
 
 //#############################################################################
-// Card's suit
+// Command line options for simulating
 //
-#[derive(Copy, Clone, Debug, PartialEq)]
-enum Suit {
-    HEARTS,
-    DIAMONDS,
-    CLUBS,
-    SPADES,
+#[derive(Debug)]
+struct SimulateOptions {
+    rounds: u64,
+    rules: RuleSet,
+    strategy: String,
+    seed: Option<u64>,
+    threads: usize,
+    precision: Option<f64>,
+    log_every: Option<u64>,
+    counting: CountingSystem,
+    betting: BetRamp,
+    unit: Money,
+    rounds_per_hour: f64,
+    deviations: DeviationTable,
+    deviation_gains: bool,
 }
 
-impl Suit {
+impl Default for SimulateOptions {
+    fn default() -> Self {
+        Self {
+            rounds: 1_000_000,
+            rules: RuleSet::default(),
+            strategy: "basic".to_string(),
+            seed: None,
+            threads: SimulationConfig::default().threads,
+            precision: None,
+            log_every: None,
+            counting: CountingSystem::default(),
+            betting: BetRamp::flat(),
+            unit: Money::dollars(10),
+            rounds_per_hour: 100.0,
+            deviations: DeviationTable::default(),
+            deviation_gains: false,
+        }
+    }
+}
 
-    /// Return the suit of the card as a string. For display purposes.
-    fn as_string(&self) -> String {
-        match self {
-            Suit::HEARTS => "HEARTS".to_string(),
-            Suit::DIAMONDS => "DIAMONDS".to_string(),
-            Suit::CLUBS => "CLUBS".to_string(),
-            Suit::SPADES => "SPADES".to_string(),
+impl SimulateOptions {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = SimulateOptions::default();
+        while let Some(arg) = args.next() {
+            if !options.parse_option(&arg, &mut args)? {
+                return Err(format!("unknown argument '{}'", arg));
+            }
         }
+        Ok(options)
     }
 
-    /// Return the suit of the card as a string. For display purposes.
-    fn as_character(&self) -> String {
-        match self {
-            Suit::HEARTS => "♥".to_string(),
-            Suit::DIAMONDS => "♦".to_string(),
-            Suit::CLUBS => "♣".to_string(),
-            Suit::SPADES => "♠".to_string(),
+    fn config(&self, seed: u64) -> SimulationConfig {
+        SimulationConfig {
+            rounds: self.rounds,
+            seed,
+            threads: self.threads,
+            precision: self.precision,
+            counting: self.counting.clone(),
+            betting: self.betting.clone(),
+            ..SimulationConfig::default()
         }
     }
 
-    /// Allow suit to be used in an iterator. Iteration will progress from HEARTS to SPADES.
-    /// Note: Trick is to have a static array and iterate through that.
-    fn iterator() -> Iter<'static, Suit> {
-        static SUIT: [Suit; 4] = [Suit::HEARTS, Suit::DIAMONDS, Suit::CLUBS, Suit::SPADES];
-        SUIT.iter()
+    /// Take one option and its value from the arguments, or say that it isn't
+    /// an option for simulating.
+    fn parse_option(
+        &mut self,
+        arg: &str,
+        args: &mut impl Iterator<Item = String>,
+    ) -> Result<bool, String> {
+        match arg {
+            // The starting hands dealt, one a round, so --rounds is the same
+            "--hands" | "--rounds" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("{} needs a value", arg))?;
+                self.rounds = value
+                    .replace('_', "")
+                    .parse()
+                    .map_err(|_| format!("invalid {} value '{}'", arg, value))?;
+            }
+            "--rules" => {
+                let value = args.next().ok_or("--rules needs a value")?;
+                self.rules = parse_rules(&value)?;
+            }
+            "--strategy" => {
+                self.strategy = args.next().ok_or("--strategy needs a value")?;
+            }
+            "--seed" => {
+                let value = args.next().ok_or("--seed needs a value")?;
+                let seed = value
+                    .parse()
+                    .map_err(|_| format!("invalid --seed value '{}'", value))?;
+                self.seed = Some(seed);
+            }
+            "--threads" => {
+                let value = args.next().ok_or("--threads needs a value")?;
+                self.threads = match value.parse() {
+                    Ok(threads) if threads > 0 => threads,
+                    _ => return Err(format!("invalid --threads value '{}'", value)),
+                };
+            }
+            "--precision" => {
+                let value = args.next().ok_or("--precision needs a value")?;
+                self.precision = match value.trim_end_matches('%').parse::<f64>() {
+                    Ok(percent) if percent > 0.0 => Some(percent / 100.0),
+                    _ => return Err(format!("invalid --precision value '{}'", value)),
+                };
+            }
+            "--log-every" => {
+                let value = args.next().ok_or("--log-every needs a value")?;
+                self.log_every = match value.replace('_', "").parse() {
+                    Ok(rounds) if rounds > 0 => Some(rounds),
+                    _ => return Err(format!("invalid --log-every value '{}'", value)),
+                };
+            }
+            "--count" => {
+                let value = args.next().ok_or("--count needs a value")?;
+                self.counting = parse_counting(&value)?;
+            }
+            "--ramp" => {
+                let value = args.next().ok_or("--ramp needs a value")?;
+                self.betting = BetRamp::load(&value).map_err(|error| error.to_string())?;
+            }
+            "--unit" => {
+                let value = args.next().ok_or("--unit needs a value")?;
+                let unit: Money = value.parse()?;
+                if unit <= Money::ZERO {
+                    return Err(format!("--unit must be more than zero, not {}", unit));
+                }
+                self.unit = unit;
+            }
+            "--rounds-per-hour" => {
+                let value = args.next().ok_or("--rounds-per-hour needs a value")?;
+                self.rounds_per_hour = match value.parse() {
+                    Ok(rounds) if rounds > 0.0 => rounds,
+                    _ => return Err(format!("invalid --rounds-per-hour value '{}'", value)),
+                };
+            }
+            "--deviations" => {
+                let value = args.next().ok_or("--deviations needs a value")?;
+                for name in value.split(',') {
+                    let table = match DeviationTable::preset(name) {
+                        Some(table) => table,
+                        None => DeviationTable::load(name).map_err(|error| match error {
+                            ChartError::Io(..) => error.to_string(),
+                            error => format!("{}: {}", name, error),
+                        })?,
+                    };
+                    self.deviations.extend(&table);
+                }
+            }
+            "--deviation-gains" => self.deviation_gains = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
     }
 }
 
 //#############################################################################
-// A single playing card
+// Command line options for working out deviation indices
 //
-#[derive(Copy, Clone, PartialEq)]
-struct Card {
-    rank: Rank,
-    suit: Suit,
-}
-
-impl Card {
-    fn new(rank: Rank, suit: Suit) -> Self {
-        Self { rank, suit }
-    }
-}
-
-impl fmt::Display for Card {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}{}", self.rank.as_character(), self.suit.as_character())
-    }
+#[derive(Debug)]
+struct IndicesOptions {
+    rules: RuleSet,
+    counting: CountingSystem,
+    threads: usize,
+    save: Option<String>,
 }
 
-impl fmt::Debug for Card {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{} of {}", self.rank.as_string(), self.suit.as_string())
+impl IndicesOptions {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = IndicesOptions {
+            rules: RuleSet::default(),
+            counting: CountingSystem::default(),
+            threads: SimulationConfig::default().threads,
+            save: None,
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--rules" => {
+                    let value = args.next().ok_or("--rules needs a value")?;
+                    options.rules = parse_rules(&value)?;
+                }
+                "--count" => {
+                    let value = args.next().ok_or("--count needs a value")?;
+                    options.counting = parse_counting(&value)?;
+                }
+                "--threads" => {
+                    let value = args.next().ok_or("--threads needs a value")?;
+                    options.threads = match value.parse() {
+                        Ok(threads) if threads > 0 => threads,
+                        _ => return Err(format!("invalid --threads value '{}'", value)),
+                    };
+                }
+                "--save" => {
+                    let value = args.next().ok_or("--save needs a value")?;
+                    options.save = Some(value);
+                }
+                "-h" | "--help" => {
+                    println!("{}", USAGE);
+                    process::exit(0);
+                }
+                _ => return Err(format!("unknown argument '{}'", arg)),
+            }
+        }
+        Ok(options)
     }
 }
 
 //#############################################################################
-// A bunch of playing cards
+// Command line options for sizing a bankroll
 //
-// A tuple struct to implement manipulation of a vec of Card. Deref to allow
-// iteration, Display for outputting and new() for creation.
+// The win rate is given in betting units per round, or else measured by
+// simulating with the simulate options, which also set up the game for
+// simulated trips.
 //
-// Note: See https://github.com/apolitical/impl-display-for-vec
-#[derive(Debug, PartialEq)]
-struct Cards(pub Vec<Card>);
-
-// Allows code like the following to be used:
-//  ```deck.cards.iter().for_each(|card| println!("{}", card));```
-impl std::ops::Deref for Cards {
-    type Target = Vec<Card>;
+#[derive(Debug)]
+struct RiskOptions {
+    game: SimulateOptions,
+    bankroll: Option<Money>,
+    ev: Option<f64>,
+    sd: Option<f64>,
+    risk: f64,
+    trip_rounds: Option<u64>,
+    trips: u64,
+}
 
-    fn deref(&self) -> &Self::Target {
-        &self.0
+impl RiskOptions {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = RiskOptions {
+            game: SimulateOptions::default(),
+            bankroll: None,
+            ev: None,
+            sd: None,
+            risk: 0.05,
+            trip_rounds: None,
+            trips: 0,
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--bankroll" => {
+                    let value = args.next().ok_or("--bankroll needs a value")?;
+                    let bankroll: Money = value.parse()?;
+                    if bankroll <= Money::ZERO {
+                        return Err(format!(
+                            "--bankroll must be more than zero, not {}",
+                            bankroll
+                        ));
+                    }
+                    options.bankroll = Some(bankroll);
+                }
+                "--ev" => {
+                    let value = args.next().ok_or("--ev needs a value")?;
+                    options.ev = match value.parse::<f64>() {
+                        Ok(ev) if ev.is_finite() => Some(ev),
+                        _ => return Err(format!("invalid --ev value '{}'", value)),
+                    };
+                }
+                "--sd" => {
+                    let value = args.next().ok_or("--sd needs a value")?;
+                    options.sd = match value.parse::<f64>() {
+                        Ok(sd) if sd > 0.0 && sd.is_finite() => Some(sd),
+                        _ => return Err(format!("invalid --sd value '{}'", value)),
+                    };
+                }
+                "--risk" => {
+                    let value = args.next().ok_or("--risk needs a value")?;
+                    options.risk = match value.trim_end_matches('%').parse::<f64>() {
+                        Ok(percent) if percent > 0.0 && percent < 100.0 => percent / 100.0,
+                        _ => return Err(format!("invalid --risk value '{}'", value)),
+                    };
+                }
+                "--trip-rounds" => {
+                    let value = args.next().ok_or("--trip-rounds needs a value")?;
+                    options.trip_rounds = match value.replace('_', "").parse() {
+                        Ok(rounds) if rounds > 0 => Some(rounds),
+                        _ => return Err(format!("invalid --trip-rounds value '{}'", value)),
+                    };
+                }
+                "--trips" => {
+                    let value = args.next().ok_or("--trips needs a value")?;
+                    options.trips = value
+                        .replace('_', "")
+                        .parse()
+                        .map_err(|_| format!("invalid --trips value '{}'", value))?;
+                }
+                arg => {
+                    if !options.game.parse_option(arg, &mut args)? {
+                        return Err(format!("unknown argument '{}'", arg));
+                    }
+                }
+            }
+        }
+        if options.bankroll.is_none() {
+            return Err("--bankroll is needed".to_string());
+        }
+        if options.ev.is_some() != options.sd.is_some() {
+            return Err("--ev and --sd go together".to_string());
+        }
+        if options.trips > 0 && options.trip_rounds.is_none() {
+            return Err("--trips needs --trip-rounds".to_string());
+        }
+        Ok(options)
     }
 }
 
-impl std::ops::DerefMut for Cards {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+/// A preset rule set by name, or else one loaded from a TOML file.
+fn parse_rules(value: &str) -> Result<RuleSet, String> {
+    match RuleSet::preset(value) {
+        Some(rules) => Ok(rules),
+        None => RuleSet::load(value).map_err(|error| error.to_string()),
     }
 }
 
-// Allows code like the following to be used:
-//  ```println!("{}", Cards(deck.cards().to_vec()));```
-impl fmt::Display for Cards {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        self.iter().fold(Ok(()), |result, card| {
-            result.and_then(|_| write!(f, "{}, ", card))
-        })
+/// A counting system by name, or else one loaded from a TOML file.
+fn parse_counting(value: &str) -> Result<CountingSystem, String> {
+    match CountingSystem::preset(value) {
+        Some(counting) => Ok(counting),
+        None => CountingSystem::load(value).map_err(|error| error.to_string()),
     }
 }
 
-impl Cards {
-    fn new() -> Self {
-        Cards(Vec::<Card>::new())
+/// Basic strategy, composition-dependent strategy or a chart from a file.
+fn load_strategy(name: &str, rules: &RuleSet) -> Result<Box<dyn Strategy + Sync>, String> {
+    match name {
+        "basic" => Ok(Box::new(BasicStrategy::new(rules))),
+        "cd" => Ok(Box::new(CompositionStrategy::generate(rules))),
+        path => match ChartStrategy::load(path) {
+            Ok(strategy) => Ok(Box::new(strategy)),
+            Err(error) => Err(error.to_string()),
+        },
     }
 }
 
-//#############################################################################
-// The deck of cards that are used to deal to the players from
-//
-#[derive(Debug)]
-struct Deck {
-    cards: Cards,
+/// Dollars to so many places with any sign before the $, e.g. "-$5.47", and
+/// with a + too if `signed`.
+fn format_dollars(dollars: f64, places: usize, signed: bool) -> String {
+    let sign = if dollars < 0.0 {
+        "-"
+    } else if signed {
+        "+"
+    } else {
+        ""
+    };
+    format!("{}${:.*}", sign, places, dollars.abs())
 }
 
-impl Deck {
-    fn new() -> Self {
-        let mut cards = Cards::new();
-        for s in Suit::iterator() {
-            for r in Rank::iterator() {
-                cards.push(Card::new(*r, *s));
-            }
-        }
-        Self { cards }
+//#############################################################################
+// Play a lot of rounds without anyone at the terminal and report how the
+// strategy did
+//
+fn simulate_command(args: impl Iterator<Item = String>) {
+    let options = SimulateOptions::parse(args).unwrap_or_else(|message| {
+        eprintln!("{}\n{}", message, USAGE);
+        process::exit(2);
+    });
+    let strategy = load_strategy(&options.strategy, &options.rules).unwrap_or_else(|message| {
+        eprintln!("{}", message);
+        process::exit(1);
+    });
+
+    let seed = options.seed.unwrap_or_else(rand::random);
+    println!("Seed: {}", seed);
+    println!("Rules: {}", options.rules);
+    println!("Strategy: {}", options.strategy);
+    println!("Count: {}", options.counting);
+    println!("Betting: {}, unit {}", options.betting, options.unit);
+    if !options.deviations.is_empty() {
+        println!("Deviations: {}", options.deviations.len());
     }
-
-    fn shuffle(&mut self) {
-        self.cards.shuffle(&mut thread_rng());
+    println!("Threads: {}", options.threads);
+
+    let start = Instant::now();
+    let config = options.config(seed);
+    // Results are added up a chunk at a time, so log at the first chunk past
+    // each interval
+    let mut logged = 0;
+    let log = |so_far: &SimulationStats| {
+        let Some(every) = options.log_every else {
+            return;
+        };
+        if so_far.rounds / every > logged {
+            logged = so_far.rounds / every;
+            let (low, high) = so_far.confidence_interval();
+            println!(
+                "After {} rounds: EV {:+.4}% ± {:.4}%",
+                so_far.rounds,
+                so_far.ev() * 100.0,
+                (high - low) / 2.0 * 100.0
+            );
+        }
+    };
+    let (stats, gains) = if options.deviation_gains {
+        let (stats, gains) = deviation_gains(
+            &options.rules,
+            strategy.as_ref(),
+            &options.deviations,
+            &config,
+        );
+        (stats, Some(gains))
+    } else {
+        let strategy = DeviationStrategy::new(strategy.as_ref(), options.deviations.clone());
+        let stats = simulate_with_progress(&options.rules, &strategy, &config, log);
+        (stats, None)
+    };
+    let seconds = start.elapsed().as_secs_f64();
+
+    println!("{}", stats);
+    let (hourly_ev, hourly_sd) = (
+        stats.hourly_ev(options.rounds_per_hour),
+        stats.hourly_standard_deviation(options.rounds_per_hour),
+    );
+    let dollars = options.unit.as_cents_f64() / 100.0;
+    println!(
+        "Per hour at {} rounds: EV {:+.2} units ({}), SD {:.2} units ({})",
+        options.rounds_per_hour,
+        hourly_ev,
+        format_dollars(hourly_ev * dollars, 2, true),
+        hourly_sd,
+        format_dollars(hourly_sd * dollars, 2, false)
+    );
+    if let Some(gains) = &gains {
+        println!("EV each deviation adds per round, with 95% confidence:");
+        for (deviation, gain) in options.deviations.deviations().iter().zip(gains) {
+            let error = match gain.half_width() {
+                Some(half_width) => format!("{:.4}%", half_width * 100.0),
+                None => "n/a".to_string(),
+            };
+            println!(
+                "  {:<24} {:+.4}% ± {}",
+                deviation.to_string(),
+                gain.gain * 100.0,
+                error
+            );
+        }
     }
+    // Every round is played again without each deviation for the gains
+    let passes = gains.map_or(1, |gains| gains.len() + 1) as f64;
+    println!(
+        "Time: {:.2}s, {:.0} hands per second, {:.0} rounds per second",
+        seconds,
+        stats.hands as f64 * passes / seconds,
+        (stats.rounds + stats.rounds_sat_out) as f64 * passes / seconds
+    );
+}
 
-    fn draw_card(&mut self) -> Option<Card> {
-        self.cards.pop()
+//#############################################################################
+// Work out where counting should change basic strategy, and print the
+// deviations or save them for `blackjack simulate --deviations`
+//
+fn indices_command(args: impl Iterator<Item = String>) {
+    let options = IndicesOptions::parse(args).unwrap_or_else(|message| {
+        eprintln!("{}\n{}", message, USAGE);
+        process::exit(2);
+    });
+    println!("Rules: {}", options.rules);
+    println!("Count: {}", options.counting);
+
+    let start = Instant::now();
+    let mut generator = IndexGenerator::new(&options.rules, &options.counting);
+    generator.threads = options.threads;
+    let table = generator.generate();
+    for deviation in table.deviations() {
+        println!("  {}", deviation);
     }
+    println!("Time: {:.2}s", start.elapsed().as_secs_f64());
 
-    fn number_of_cards(&self) -> u8 {
-        // Note: try_into().unwrap() is converting usize into u8
-        self.cards.len().try_into().unwrap()
+    if let Some(path) = &options.save {
+        if let Err(error) = table.save(path) {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+        println!("Deviations saved to {}", path);
     }
 }
 
 //#############################################################################
-// A player (or the dealer) who holds a hand of cards
+// Work out the risk of losing a bankroll from the win rate and how big it
+// needs to be, and check the risk by playing trips with it
 //
-#[derive(Debug)]
-struct Player {
-    hand: Cards,
-}
+fn risk_command(args: impl Iterator<Item = String>) {
+    let options = RiskOptions::parse(args).unwrap_or_else(|message| {
+        eprintln!("{}\n{}", message, USAGE);
+        process::exit(2);
+    });
+    let game = &options.game;
+    let bankroll = options.bankroll.expect("checked when parsing");
+    let units = bankroll.as_cents_f64() / game.unit.as_cents_f64();
+    let unit_dollars = game.unit.as_cents_f64() / 100.0;
+
+    // The game is only needed to measure the win rate or play trips
+    let seed = game.seed.unwrap_or_else(rand::random);
+    let given = options.ev.zip(options.sd);
+    let loaded = (given.is_none() || options.trips > 0).then(|| {
+        let strategy = load_strategy(&game.strategy, &game.rules).unwrap_or_else(|message| {
+            eprintln!("{}", message);
+            process::exit(1);
+        });
+        println!("Seed: {}", seed);
+        println!("Rules: {}", game.rules);
+        println!("Strategy: {}", game.strategy);
+        println!("Count: {}", game.counting);
+        println!("Betting: {}, unit {}", game.betting, game.unit);
+        if !game.deviations.is_empty() {
+            println!("Deviations: {}", game.deviations.len());
+        }
+        strategy
+    });
+    let strategy = loaded
+        .as_ref()
+        .map(|strategy| DeviationStrategy::new(strategy.as_ref(), game.deviations.clone()));
+    let rate = match (given, &strategy) {
+        (Some((ev, sd)), _) => WinRate::new(ev, sd),
+        (None, Some(strategy)) => {
+            let stats = simulate_parallel(&game.rules, strategy, &game.config(seed));
+            println!("Simulated {} rounds", stats.rounds + stats.rounds_sat_out);
+            WinRate::from_stats(&stats)
+        }
+        (None, None) => unreachable!("the strategy is loaded to measure the win rate"),
+    };
 
-impl Player {
-    fn new() -> Self {
-        let hand = Cards::new();
-        Self { hand }
+    println!("Win rate: {}", rate);
+    println!(
+        "Bankroll: {}, {:.0} units of {}",
+        bankroll, units, game.unit
+    );
+    println!(
+        "Risk of ruin: {:.2}% playing on forever",
+        rate.risk_of_ruin(units) * 100.0
+    );
+    if let Some(rounds) = options.trip_rounds {
+        println!(
+            "Risk of ruin: {:.2}% within {} rounds",
+            rate.risk_of_ruin_within(units, rounds as f64) * 100.0,
+            rounds
+        );
+    }
+    match (rate.bankroll_for(options.risk), rate.n0(), rate.score()) {
+        (Some(needed), Some(n0), Some(score)) => {
+            println!(
+                "Bankroll for a {}% risk of ruin: {:.0} units ({})",
+                options.risk * 100.0,
+                needed,
+                format_dollars(needed * unit_dollars, 0, false)
+            );
+            println!(
+                "N0: {:.0} rounds, {:.0} hours at {} rounds per hour",
+                n0,
+                n0 / game.rounds_per_hour,
+                game.rounds_per_hour
+            );
+            println!("SCORE: {} per 100 rounds", format_dollars(score, 2, false));
+        }
+        _ => println!("Without an edge no bankroll is big enough"),
     }
 
-    fn add_card(self: &mut Player, card: Option<Card>) {
-        self.hand.push(card.unwrap())
+    if let (Some(strategy), Some(rounds), true) =
+        (&strategy, options.trip_rounds, options.trips > 0)
+    {
+        let config = SimulationConfig {
+            rounds,
+            ..game.config(seed)
+        };
+        let trips = simulate_trips(&game.rules, strategy, &config, units, options.trips);
+        println!(
+            "Simulated {} trips of {} rounds: {:.2}% ruined ± {:.2}%",
+            trips.trips,
+            rounds,
+            trips.risk_of_ruin() * 100.0,
+            trips.standard_error() * 1.96 * 100.0
+        );
     }
+}
 
-    fn get_hand_value(self: &mut Player) -> u8 {
-        let mut value = 0;
-        let mut aces = 0;
-
-        self.hand.iter().for_each(|card| {
-            let mut card_value = card.rank.as_number();
-            if card_value >= 10 {
-                card_value = 10;
-            } else if card_value == 1 {
-                aces += 1;
-                card_value = 11;
+//#############################################################################
+// Ask the player what to do until they give one of the allowed actions. The
+// first letter of the action is enough. Stands at the end of input.
+//
+fn ask_action(allowed: &[Action]) -> Action {
+    let names: Vec<String> = allowed.iter().map(|action| action.to_string()).collect();
+    loop {
+        print!("{}? ", names.join(", "));
+        io::stdout().flush().unwrap();
+
+        let mut line = String::new();
+        if io::stdin().read_line(&mut line).unwrap() == 0 {
+            return Action::Stand;
+        }
+        let answer = line.trim().to_lowercase();
+        if answer.is_empty() {
+            continue;
+        }
+        for (action, name) in allowed.iter().zip(&names) {
+            if name.starts_with(&answer) {
+                return *action;
             }
-            value += card_value;
-
-        });
+        }
+        println!("Please enter one of: {}", names.join(", "));
+    }
+}
 
-        while value > 21 && aces > 0 {
-            value -= 10;
-            aces -= 1;
+//#############################################################################
+// Ask the player a yes or no question. No at the end of input.
+//
+fn ask_yes_no(question: &str) -> bool {
+    loop {
+        print!("{} (y/n)? ", question);
+        io::stdout().flush().unwrap();
+
+        let mut line = String::new();
+        if io::stdin().read_line(&mut line).unwrap() == 0 {
+            return false;
+        }
+        match line.trim().to_lowercase().as_str() {
+            "y" | "yes" => return true,
+            "n" | "no" => return false,
+            _ => println!("Please enter y or n"),
         }
-        value
     }
 }
 
 //#############################################################################
 //
 fn main() {
-    // Create the players
-    let mut player = Player::new();
-    let mut dealer = Player::new();
+    let mut args = std::env::args().skip(1).peekable();
+    match args.peek().map(String::as_str) {
+        Some("simulate") => return simulate_command(args.skip(1)),
+        Some("indices") => return indices_command(args.skip(1)),
+        Some("risk") => return risk_command(args.skip(1)),
+        _ => {}
+    }
+
+    let options = Options::parse(args).unwrap_or_else(|message| {
+        eprintln!("{}\n{}", message, USAGE);
+        process::exit(2);
+    });
+
+    // Write out the rules for use later instead of playing
+    if let Some(path) = &options.save_rules {
+        if let Err(error) = options.rules.save(path) {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+        println!("Rules saved to {}", path);
+        return;
+    }
 
-    // Create the deck and shuffle it
-    let mut deck = Deck::new();
-    deck.shuffle();
+    // Write out basic strategy for the rules so that it can be edited
+    if let Some(path) = &options.save_chart {
+        if let Err(error) = BasicStrategy::new(&options.rules).chart().save(path) {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+        println!("Basic strategy chart saved to {}", path);
+        return;
+    }
+
+    // Work out composition-dependent strategy for the rules, write out the
+    // chart it makes and show where it differs from basic strategy
+    if let Some(path) = &options.save_cd_chart {
+        let strategy = CompositionStrategy::generate(&options.rules);
+        if let Err(error) = strategy.chart().save(path) {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+        println!("Composition-dependent chart saved to {}", path);
+        println!("Differences from basic strategy for {}:", options.rules);
+        strategy
+            .differences()
+            .iter()
+            .for_each(|difference| println!("{}", difference));
+        return;
+    }
+
+    // Play from a chart if one was given, otherwise from basic strategy
+    let chart = options.chart.as_deref().unwrap_or("basic");
+    let strategy = load_strategy(chart, &options.rules).unwrap_or_else(|message| {
+        eprintln!("{}", message);
+        process::exit(1);
+    });
+
+    // Pick a seed if one wasn't given and show it so the game can be replayed
+    let seed = options.seed.unwrap_or_else(rand::random);
+    println!("Seed: {}", seed);
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+
+    // Create the shoe for the table's rules and shuffle it
+    let rules = options.rules;
+    println!("Rules: {}", rules);
+    println!("House edge: {:.2}%", rules.house_edge());
+    let mut deck = rules.shoe();
+    deck.shuffle_with(&mut rng);
 
     // Deal out the hands
-    player.add_card(deck.draw_card());
-    player.add_card(deck.draw_card());
+    let mut round = Round::deal(&mut deck, &rules, options.bet);
+    println!("Dealer shows: {}", round.upcard());
+
+    // Dealer shows an ace so insurance is offered before anything else
+    if round.insurance_offered() {
+        let hand = round.player().hand(0);
+        println!(
+            "Player hand: {}value: {}",
+            hand.cards(),
+            hand.get_hand_value()
+        );
+        let question = if hand.has_blackjack() {
+            "Take even money"
+        } else {
+            "Take insurance"
+        };
+        let take = if options.auto {
+            let take = strategy.take_insurance(hand.cards(), &rules);
+            println!("{}? {}", question, if take { "yes" } else { "no" });
+            take
+        } else {
+            ask_yes_no(question)
+        };
+        round.take_insurance(take, &mut deck);
+    }
 
-    dealer.add_card(deck.draw_card());
-    dealer.add_card(deck.draw_card());
+    // Let the player, or the strategy with --auto, play each of their hands
+    // until the round is over
+    while !round.is_finished() {
+        let index = round.current_hand();
+        let hand = round.player().hand(index);
+        println!(
+            "Player hand {}: {}value: {}",
+            index + 1,
+            hand.cards(),
+            hand.get_hand_value()
+        );
+        let allowed = round.allowed_actions();
+        let action = if options.auto {
+            let action = strategy.decide(hand.cards(), round.upcard(), &allowed, &rules);
+            println!("Strategy says: {}", action);
+            action
+        } else {
+            ask_action(&allowed)
+        };
+        round.act(action, &mut deck);
+    }
 
     // Show the hands
-    print!("Dealer hand: ");
-    dealer.hand.iter().for_each(|card| print!("{}, ", card));
-    println!("value: {}", dealer.get_hand_value());
-
-    print!("Player hand: ");
-    player.hand.iter().for_each(|card| print!("{}, ", card));
-    println!("value: {}", player.get_hand_value());
+    println!(
+        "Dealer hand: {}value: {}",
+        round.dealer().cards(),
+        round.dealer().get_hand_value()
+    );
 
     // Who has won?
-    if dealer.get_hand_value() > player.get_hand_value() {
-        println!("Dealer wins. Boo!");
+    if let Some(won) = round.insurance_won() {
+        if won {
+            println!("Insurance pays 2:1");
+        } else {
+            println!("Insurance lost");
+        }
     }
-    else {
-        println!("Player wins. Yae!");
+    let results = round.hand_results();
+    for (index, hand) in round.player().hands().iter().enumerate() {
+        println!(
+            "Player hand {}: {}value: {}, bet: {}. {} ({})",
+            index + 1,
+            hand.cards(),
+            hand.get_hand_value(),
+            hand.bet(),
+            round.outcomes()[index],
+            results[index]
+        );
     }
+    println!("Player's net for the round: {}", round.net());
 
     // Output whole pack using fmt::Display for Cards
     // Note: Requires to_vec() since can't copy a vec for Cards so a copy needs to be made
     println!(
-        "What's left in the deck of {} cards",
+        "What's left in the shoe of {} cards",
         deck.number_of_cards()
     );
-    println!("{}", Cards(deck.cards.to_vec()));
-    println!("{:?}", Cards(deck.cards.to_vec()));
-}
-
-//@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@
-// Tests
-//
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn card_creation() {
-        let card = Card::new(Rank::ACE, Suit::DIAMONDS);
-        assert_eq!(card.suit.as_string(), "DIAMONDS");
-        assert_eq!(card.rank.as_string(), "ACE");
-    }
-
-    #[test]
-    fn deck_has_52_cards() {
-        let deck = Deck::new();
-        assert_eq!(deck.number_of_cards(), 52);
-    }
-
-    #[test]
-    fn deck_has_been_shuffled() {
-        let mut deck = Deck::new();
-        assert_eq!(deck.cards[0], Card::new(Rank::ACE, Suit::HEARTS));
-        deck.shuffle();
-        // Note: Could fail and the top of the deck could still be ace of hearts
-        // after shuffling, maybe test a few more cards?
-        assert_ne!(deck.cards[0], Card::new(Rank::ACE, Suit::HEARTS));
-    }
-
-    #[test]
-    fn drawing_card_removes_from_deck() {
-        let mut deck = Deck::new();
-        deck.draw_card();
-        assert_eq!(deck.number_of_cards(), 51);
-    }
-
-    #[test]
-    fn adding_card_to_players_hand() {
-        let mut player = Player::new();
-        let card = Card::new(Rank::ACE, Suit::DIAMONDS);
-        player.add_card(std::option::Option::Some(card));
-        assert_eq!(player.get_hand_value(), 11);
-    }
+    println!("{}", Cards(deck.cards().to_vec()));
+    println!("{:?}", Cards(deck.cards().to_vec()));
 }
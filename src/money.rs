@@ -0,0 +1,294 @@
+//%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+// Bets, winnings and payout ratios
+//
+use std::cmp::Ordering;
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+use std::str::FromStr;
+
+//#############################################################################
+// An exact amount of money
+//
+// Held as a fraction of a cent so that paying 3:2 or 6:5 on any bet, or
+// giving back half a bet on a surrender, is exact however odd the bet. The
+// fraction is always kept in its lowest terms with a positive denominator.
+//
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Money {
+    numerator: i64,
+    denominator: i64,
+}
+
+impl Money {
+    pub const ZERO: Money = Money {
+        numerator: 0,
+        denominator: 1,
+    };
+
+    pub const fn cents(cents: i64) -> Self {
+        Self {
+            numerator: cents,
+            denominator: 1,
+        }
+    }
+
+    pub const fn dollars(dollars: i64) -> Self {
+        Self::cents(dollars * 100)
+    }
+
+    /// Multiply by the ratio `numerator / denominator`, exactly.
+    ///
+    /// # Panics
+    ///
+    /// If the denominator is zero.
+    pub fn times(&self, numerator: i64, denominator: i64) -> Self {
+        assert!(denominator != 0, "denominator must not be zero");
+        Self::reduced(
+            self.numerator as i128 * numerator as i128,
+            self.denominator as i128 * denominator as i128,
+        )
+    }
+
+    /// Half of the amount, such as the cost of insurance or a surrender.
+    pub fn half(&self) -> Self {
+        self.times(1, 2)
+    }
+
+    /// Is this a whole number of cents?
+    pub fn is_whole_cents(&self) -> bool {
+        self.denominator == 1
+    }
+
+    /// The amount in cents, if it is a whole number of them.
+    pub fn whole_cents(&self) -> Option<i64> {
+        self.is_whole_cents().then_some(self.numerator)
+    }
+
+    /// The amount in cents, for statistics where an f64 is good enough.
+    pub fn as_cents_f64(&self) -> f64 {
+        self.numerator as f64 / self.denominator as f64
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.numerator == 0
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.numerator < 0
+    }
+
+    fn reduced(numerator: i128, denominator: i128) -> Self {
+        let sign = if denominator < 0 { -1 } else { 1 };
+        let divisor = gcd(numerator.abs(), denominator.abs()).max(1);
+        Self {
+            numerator: i64::try_from(sign * numerator / divisor).expect("amount overflowed"),
+            denominator: i64::try_from(sign * denominator / divisor).expect("amount overflowed"),
+        }
+    }
+}
+
+fn gcd(mut a: i128, mut b: i128) -> i128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+impl Default for Money {
+    fn default() -> Self {
+        Self::ZERO
+    }
+}
+
+impl Add for Money {
+    type Output = Money;
+
+    fn add(self, other: Money) -> Money {
+        Self::reduced(
+            self.numerator as i128 * other.denominator as i128
+                + other.numerator as i128 * self.denominator as i128,
+            self.denominator as i128 * other.denominator as i128,
+        )
+    }
+}
+
+impl Sub for Money {
+    type Output = Money;
+
+    fn sub(self, other: Money) -> Money {
+        self + -other
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+
+    fn neg(self) -> Money {
+        Self {
+            numerator: -self.numerator,
+            denominator: self.denominator,
+        }
+    }
+}
+
+impl AddAssign for Money {
+    fn add_assign(&mut self, other: Money) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for Money {
+    fn sub_assign(&mut self, other: Money) {
+        *self = *self - other;
+    }
+}
+
+impl Sum for Money {
+    fn sum<I: Iterator<Item = Money>>(iter: I) -> Self {
+        iter.fold(Money::ZERO, |total, amount| total + amount)
+    }
+}
+
+impl Ord for Money {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.numerator as i128 * other.denominator as i128)
+            .cmp(&(other.numerator as i128 * self.denominator as i128))
+    }
+}
+
+impl PartialOrd for Money {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Shown as dollars and cents, with any fraction of a cent after it,
+// e.g. "$7.50" or "-$0.01 1/2¢"
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.is_negative() { "-" } else { "" };
+        let numerator = self.numerator.abs();
+        let cents = numerator / self.denominator;
+        write!(f, "{}${}.{:02}", sign, cents / 100, cents % 100)?;
+        let remainder = numerator % self.denominator;
+        if remainder != 0 {
+            write!(f, " {}/{}¢", remainder, self.denominator)?;
+        }
+        Ok(())
+    }
+}
+
+// Parses dollars with optional cents, e.g. "10", "$12.5" or "-7.25"
+impl FromStr for Money {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid amount '{}'", text);
+        let (sign, digits) = match text.trim().strip_prefix('-') {
+            Some(digits) => (-1, digits),
+            None => (1, text.trim()),
+        };
+        let digits = digits.strip_prefix('$').unwrap_or(digits);
+        let (dollars, cents) = match digits.split_once('.') {
+            Some((dollars, cents)) => (dollars, cents),
+            None => (digits, ""),
+        };
+        let all_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+        if dollars.is_empty() || cents.len() > 2 || !all_digits(dollars) || !all_digits(cents) {
+            return Err(invalid());
+        }
+        let dollars: i64 = dollars.parse().map_err(|_| invalid())?;
+        let cents: i64 = match cents.len() {
+            0 => 0,
+            1 => cents.parse::<i64>().map_err(|_| invalid())? * 10,
+            _ => cents.parse().map_err(|_| invalid())?,
+        };
+        Ok(Money::cents(sign * (dollars * 100 + cents)))
+    }
+}
+
+//#############################################################################
+// What a winning bet is paid, as in "3:2" meaning 3 paid for every 2 bet
+//
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Payout {
+    pub paid: u32,
+    pub staked: u32,
+}
+
+impl Payout {
+    pub const EVEN_MONEY: Payout = Payout { paid: 1, staked: 1 };
+    pub const THREE_TO_TWO: Payout = Payout { paid: 3, staked: 2 };
+    pub const SIX_TO_FIVE: Payout = Payout { paid: 6, staked: 5 };
+    pub const TWO_TO_ONE: Payout = Payout { paid: 2, staked: 1 };
+
+    /// The winnings on the given bet, not including the bet itself.
+    pub fn on(&self, bet: Money) -> Money {
+        bet.times(self.paid as i64, self.staked as i64)
+    }
+
+    /// The payout as a number, e.g. 1.5 for 3:2.
+    pub fn as_f64(&self) -> f64 {
+        self.paid as f64 / self.staked as f64
+    }
+}
+
+impl fmt::Display for Payout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.paid, self.staked)
+    }
+}
+
+// Parses a payout written as "3:2"
+impl FromStr for Payout {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid payout '{}', expected e.g. \"3:2\"", text);
+        let (paid, staked) = text.trim().split_once(':').ok_or_else(invalid)?;
+        let paid = paid.trim().parse().map_err(|_| invalid())?;
+        let staked = staked.trim().parse().map_err(|_| invalid())?;
+        Ok(Payout { paid, staked })
+    }
+}
+
+//@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@
+// Tests
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn six_to_five_on_odd_bet_is_exact() {
+        // $5.01 at 6:5 is $6.012
+        let won = Payout::SIX_TO_FIVE.on(Money::cents(501));
+        assert!(!won.is_whole_cents());
+        assert_eq!(won.times(5, 1), Money::cents(3006));
+        assert_eq!(won.to_string(), "$6.01 1/5¢");
+    }
+
+    #[test]
+    fn halves_add_back_up() {
+        let bet = Money::cents(25);
+        assert_eq!(bet.half() + bet.half(), bet);
+        assert_eq!((-bet.half()).to_string(), "-$0.12 1/2¢");
+    }
+
+    #[test]
+    fn parse_dollars_and_cents() {
+        assert_eq!("10".parse(), Ok(Money::dollars(10)));
+        assert_eq!("$12.5".parse(), Ok(Money::cents(1250)));
+        assert_eq!("7.25".parse(), Ok(Money::cents(725)));
+        assert_eq!("-$7.25".parse(), Ok(Money::cents(-725)));
+        assert!("7.255".parse::<Money>().is_err());
+        assert!("ten".parse::<Money>().is_err());
+    }
+
+    #[test]
+    fn parse_payout() {
+        assert_eq!("6:5".parse(), Ok(Payout::SIX_TO_FIVE));
+        assert!("6/5".parse::<Payout>().is_err());
+    }
+}
@@ -0,0 +1,160 @@
+//%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+// Risk of ruin and how big a bankroll needs to be
+//
+use crate::simulation::SimulationStats;
+use std::fmt;
+
+//#############################################################################
+// How much a player wins on average each round and how much that swings
+//
+// Both are in betting units per round dealt, counting rounds sat out while
+// wonging, since they take time too. The player's results are treated as a
+// random walk with this drift and spread, which is close enough once a
+// bankroll is a good many bets.
+//
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct WinRate {
+    pub ev: f64,
+    pub standard_deviation: f64,
+}
+
+impl WinRate {
+    pub fn new(ev: f64, standard_deviation: f64) -> Self {
+        Self {
+            ev,
+            standard_deviation,
+        }
+    }
+
+    /// The win rate measured by a simulation.
+    pub fn from_stats(stats: &SimulationStats) -> Self {
+        Self::new(stats.hourly_ev(1.0), stats.hourly_standard_deviation(1.0))
+    }
+
+    pub fn variance(&self) -> f64 {
+        self.standard_deviation * self.standard_deviation
+    }
+
+    /// The chance of ever losing the whole bankroll, given in units, playing
+    /// on forever. Certain without an edge.
+    pub fn risk_of_ruin(&self, bankroll: f64) -> f64 {
+        if self.ev <= 0.0 {
+            return 1.0;
+        }
+        (-2.0 * self.ev * bankroll / self.variance()).exp()
+    }
+
+    /// The chance of losing the whole bankroll at some point within a number
+    /// of rounds, such as over a trip.
+    pub fn risk_of_ruin_within(&self, bankroll: f64, rounds: f64) -> f64 {
+        if rounds <= 0.0 {
+            return 0.0;
+        }
+        if self.standard_deviation == 0.0 {
+            return if bankroll + self.ev * rounds <= 0.0 {
+                1.0
+            } else {
+                0.0
+            };
+        }
+        // Ending up below zero, plus the paths that dipped below zero and
+        // came back, by reflecting them about it
+        let spread = self.standard_deviation * rounds.sqrt();
+        let drift = self.ev * rounds;
+        let ended_below = normal_cdf((-bankroll - drift) / spread);
+        let came_back = (-2.0 * self.ev * bankroll / self.variance()
+            + normal_cdf((-bankroll + drift) / spread).ln())
+        .exp();
+        (ended_below + came_back).min(1.0)
+    }
+
+    /// The bankroll in units that gives the risk of ruin, if there is an
+    /// edge to play with.
+    pub fn bankroll_for(&self, risk_of_ruin: f64) -> Option<f64> {
+        (self.ev > 0.0).then(|| -self.variance() * risk_of_ruin.ln() / (2.0 * self.ev))
+    }
+
+    /// N0, the rounds it takes for the expected win to reach one standard
+    /// deviation of the results, the long run in which skill shows.
+    pub fn n0(&self) -> Option<f64> {
+        (self.ev > 0.0).then(|| self.variance() / (self.ev * self.ev))
+    }
+
+    /// SCORE, the standardized win rate: the dollars won per 100 rounds
+    /// with a $10,000 bankroll, betting it in proportion to the edge as the
+    /// Kelly criterion says. Games with different rules, counts and ramps can
+    /// be compared by it.
+    pub fn score(&self) -> Option<f64> {
+        (self.ev > 0.0).then(|| 1e6 * self.ev * self.ev / self.variance())
+    }
+}
+
+// Shown as e.g. "+0.0150 units per round, SD 3.40"
+impl fmt::Display for WinRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:+.4} units per round, SD {:.2}",
+            self.ev, self.standard_deviation
+        )
+    }
+}
+
+/// The chance of a standard normal variable being below x.
+fn normal_cdf(x: f64) -> f64 {
+    0.5 * erfc(-x / std::f64::consts::SQRT_2)
+}
+
+// The complementary error function, to within about 1e-7 (Abramowitz and
+// Stegun 7.1.26)
+fn erfc(x: f64) -> f64 {
+    if x < 0.0 {
+        return 2.0 - erfc(-x);
+    }
+    let t = 1.0 / (1.0 + 0.3275911 * x);
+    let poly = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    poly * (-x * x).exp()
+}
+
+//@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@
+// Tests
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64, within: f64) -> bool {
+        (a - b).abs() <= within
+    }
+
+    #[test]
+    fn normal_probabilities() {
+        assert!(close(normal_cdf(0.0), 0.5, 1e-7));
+        assert!(close(normal_cdf(1.96), 0.975, 1e-4));
+        assert!(close(normal_cdf(-1.0), 0.158655, 1e-6));
+    }
+
+    #[test]
+    fn bankrolls_and_risk() {
+        // A typical counter: 1.5% of a unit a round with an SD of 3 units
+        let rate = WinRate::new(0.015, 3.0);
+        let bankroll = rate.bankroll_for(0.05).unwrap();
+        assert!(close(bankroll, 898.7, 0.1));
+        assert!(close(rate.risk_of_ruin(bankroll), 0.05, 1e-12));
+        assert!(close(rate.n0().unwrap(), 40_000.0, 1e-6));
+        assert!(close(rate.score().unwrap(), 25.0, 1e-9));
+
+        // Over a trip the risk is less, and tends to the lifetime risk
+        let trip = rate.risk_of_ruin_within(bankroll, 10_000.0);
+        assert!(trip > 0.0 && trip < 0.05);
+        assert!(close(rate.risk_of_ruin_within(bankroll, 1e9), 0.05, 1e-6));
+
+        // Without an edge ruin comes in the end
+        let losing = WinRate::new(-0.005, 1.15);
+        assert_eq!(losing.risk_of_ruin(100.0), 1.0);
+        assert_eq!(losing.bankroll_for(0.05), None);
+        assert!(losing.risk_of_ruin_within(100.0, 1e7) > 0.999);
+    }
+}
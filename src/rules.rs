@@ -0,0 +1,620 @@
+//%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+// Table rules that change how a round is played
+//
+use crate::card::{Card, Rank};
+use crate::hand::HandValue;
+use crate::money::Payout;
+use crate::shoe::Shoe;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+//#############################################################################
+// When the dealer stops drawing
+//
+// Both rules have the dealer draw to 17. They differ on a soft 17 (an ace
+// counted as 11 plus six), where S17 stands and H17 draws another card.
+//
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum DealerRule {
+    #[default]
+    #[serde(rename = "S17")]
+    StandSoft17,
+    #[serde(rename = "H17")]
+    HitSoft17,
+}
+
+impl DealerRule {
+    /// Does the dealer have to draw another card to a hand of this value?
+    pub fn should_hit(&self, value: HandValue) -> bool {
+        match self {
+            DealerRule::StandSoft17 => value.total < 17,
+            DealerRule::HitSoft17 => value.total < 17 || (value.total == 17 && value.soft),
+        }
+    }
+}
+
+impl fmt::Display for DealerRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DealerRule::StandSoft17 => write!(f, "S17"),
+            DealerRule::HitSoft17 => write!(f, "H17"),
+        }
+    }
+}
+
+//#############################################################################
+// Which two card hands the player may double down on
+//
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum DoubleRule {
+    #[default]
+    #[serde(rename = "any-two-cards")]
+    AnyTwoCards,
+    #[serde(rename = "9-11")]
+    NineToEleven,
+    #[serde(rename = "10-11")]
+    TenOrEleven,
+    #[serde(rename = "none")]
+    NotAllowed,
+}
+
+impl DoubleRule {
+    /// May a two card hand of this value be doubled?
+    pub fn allows(&self, value: HandValue) -> bool {
+        match self {
+            DoubleRule::AnyTwoCards => true,
+            DoubleRule::NineToEleven => (9..=11).contains(&value.total),
+            DoubleRule::TenOrEleven => (10..=11).contains(&value.total),
+            DoubleRule::NotAllowed => false,
+        }
+    }
+}
+
+impl fmt::Display for DoubleRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DoubleRule::AnyTwoCards => write!(f, "double any two cards"),
+            DoubleRule::NineToEleven => write!(f, "double 9-11"),
+            DoubleRule::TenOrEleven => write!(f, "double 10-11"),
+            DoubleRule::NotAllowed => write!(f, "no doubling"),
+        }
+    }
+}
+
+//#############################################################################
+// Whether the player may give up half their bet instead of playing the hand
+//
+// Late surrender is only offered once the dealer has checked for blackjack.
+// Early surrender is offered before the dealer checks, so it also saves half
+// the bet against a dealer blackjack.
+//
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum SurrenderRule {
+    #[default]
+    #[serde(rename = "none")]
+    NotAllowed,
+    #[serde(rename = "late")]
+    Late,
+    #[serde(rename = "early")]
+    Early,
+}
+
+impl fmt::Display for SurrenderRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SurrenderRule::NotAllowed => write!(f, "no surrender"),
+            SurrenderRule::Late => write!(f, "late surrender"),
+            SurrenderRule::Early => write!(f, "early surrender"),
+        }
+    }
+}
+
+//#############################################################################
+// All of the rules in force at a table
+//
+// A rule set is what the game engine plays to. The default is a six deck
+// shoe, S17, double any two cards with DAS, split to four hands, no surrender
+// and 3:2 for a natural, and there are presets for some well known tables.
+//
+// Rule sets can be read from and written to TOML files, one key per field.
+// Keys that are left out take their default value and unknown keys are an
+// error, e.g.
+//
+//   decks = 2
+//   dealer = "H17"
+//   double = "10-11"
+//   surrender = "late"
+//   blackjack_payout = "6:5"
+//
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RuleSet {
+    /// Number of decks in the shoe.
+    pub decks: u8,
+    /// Fraction of the shoe dealt before the cut card comes out.
+    pub penetration: f64,
+    pub dealer: DealerRule,
+    pub double: DoubleRule,
+    /// May a hand that came from a split be doubled (DAS)? Makes no
+    /// difference if doubling or splitting isn't allowed.
+    pub double_after_split: bool,
+    /// Most hands a player may have after splitting and resplitting.
+    pub max_split_hands: u8,
+    /// May aces be split again if another ace is dealt to a split ace?
+    pub resplit_aces: bool,
+    /// Do split aces get just one card each?
+    pub one_card_split_aces: bool,
+    /// May two ten valued cards of different rank, such as J+Q, be split?
+    pub split_unlike_tens: bool,
+    /// European no hole card (ENHC). The dealer takes their second card after
+    /// the player has finished and so can't peek for blackjack. A dealer
+    /// blackjack then takes all of the player's bets, doubles and splits included.
+    pub no_hole_card: bool,
+    pub surrender: SurrenderRule,
+    /// What a player natural is paid.
+    #[serde(with = "payout_as_string")]
+    pub blackjack_payout: Payout,
+}
+
+// Payouts are written as "3:2" in rules files
+mod payout_as_string {
+    use crate::money::Payout;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(payout: &Payout, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(payout)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Payout, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+//#############################################################################
+// Why a rule set could not be loaded, saved or used
+//
+#[derive(Debug)]
+pub enum RulesError {
+    /// Reading or writing the file failed.
+    Io(String, io::Error),
+    /// The file isn't valid TOML or has an unknown key or a bad value.
+    Parse(String),
+    /// A key has a value that can't be played, on its own or with other rules.
+    Invalid { key: &'static str, reason: String },
+}
+
+impl fmt::Display for RulesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RulesError::Io(path, error) => write!(f, "{}: {}", path, error),
+            RulesError::Parse(message) => write!(f, "{}", message.trim_end()),
+            RulesError::Invalid { key, reason } => write!(f, "invalid `{}`: {}", key, reason),
+        }
+    }
+}
+
+impl std::error::Error for RulesError {}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        Self {
+            decks: 6,
+            penetration: 0.75,
+            dealer: DealerRule::default(),
+            double: DoubleRule::default(),
+            double_after_split: true,
+            max_split_hands: 4,
+            resplit_aces: false,
+            one_card_split_aces: true,
+            split_unlike_tens: true,
+            no_hole_card: false,
+            surrender: SurrenderRule::default(),
+            blackjack_payout: Payout::THREE_TO_TWO,
+        }
+    }
+}
+
+impl RuleSet {
+    /// Names of the preset rule sets, for use with preset().
+    pub const PRESETS: [&'static str; 5] = [
+        "vegas-strip",
+        "downtown-vegas",
+        "atlantic-city",
+        "european",
+        "single-deck-6-5",
+    ];
+
+    /// Look up a preset rule set by name.
+    pub fn preset(name: &str) -> Option<RuleSet> {
+        match name {
+            "vegas-strip" => Some(Self::vegas_strip()),
+            "downtown-vegas" => Some(Self::downtown_vegas()),
+            "atlantic-city" => Some(Self::atlantic_city()),
+            "european" => Some(Self::european_no_hole_card()),
+            "single-deck-6-5" => Some(Self::single_deck_six_to_five()),
+            _ => None,
+        }
+    }
+
+    /// Six decks, S17, DAS, late surrender.
+    pub fn vegas_strip() -> Self {
+        Self {
+            surrender: SurrenderRule::Late,
+            ..Self::default()
+        }
+    }
+
+    /// Double deck, H17, DAS.
+    pub fn downtown_vegas() -> Self {
+        Self {
+            decks: 2,
+            penetration: 0.65,
+            dealer: DealerRule::HitSoft17,
+            ..Self::default()
+        }
+    }
+
+    /// Eight decks, S17, DAS, late surrender.
+    pub fn atlantic_city() -> Self {
+        Self {
+            decks: 8,
+            penetration: 0.8,
+            surrender: SurrenderRule::Late,
+            ..Self::default()
+        }
+    }
+
+    /// Six decks, S17, no hole card, double 9-11, no resplitting.
+    pub fn european_no_hole_card() -> Self {
+        Self {
+            double: DoubleRule::NineToEleven,
+            max_split_hands: 2,
+            no_hole_card: true,
+            ..Self::default()
+        }
+    }
+
+    /// Single deck, H17, no DAS, naturals paid 6:5.
+    pub fn single_deck_six_to_five() -> Self {
+        Self {
+            decks: 1,
+            penetration: 0.6,
+            dealer: DealerRule::HitSoft17,
+            double_after_split: false,
+            blackjack_payout: Payout::SIX_TO_FIVE,
+            ..Self::default()
+        }
+    }
+
+    /// Read a rule set from TOML and check that it can be played.
+    pub fn from_toml(text: &str) -> Result<RuleSet, RulesError> {
+        let rules: RuleSet =
+            toml::from_str(text).map_err(|error| RulesError::Parse(error.to_string()))?;
+        rules.validate()?;
+        Ok(rules)
+    }
+
+    /// Write the rule set as TOML, with every key present.
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("a rule set can always be written as TOML")
+    }
+
+    /// Load a rule set from a TOML file.
+    pub fn load(path: impl AsRef<Path>) -> Result<RuleSet, RulesError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|error| RulesError::Io(path.display().to_string(), error))?;
+        Self::from_toml(&text).map_err(|error| match error {
+            RulesError::Parse(message) => {
+                RulesError::Parse(format!("{}: {}", path.display(), message))
+            }
+            error => error,
+        })
+    }
+
+    /// Save the rule set to a TOML file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), RulesError> {
+        let path = path.as_ref();
+        fs::write(path, self.to_toml())
+            .map_err(|error| RulesError::Io(path.display().to_string(), error))
+    }
+
+    /// Check that the rules make sense together, naming the first key that
+    /// doesn't.
+    pub fn validate(&self) -> Result<(), RulesError> {
+        let invalid = |key, reason: &str| {
+            Err(RulesError::Invalid {
+                key,
+                reason: reason.to_string(),
+            })
+        };
+        if self.decks == 0 {
+            return invalid("decks", "there must be at least one deck");
+        }
+        // Dealing the whole shoe would leave nothing to finish the last round
+        if !(self.penetration > 0.0 && self.penetration < 1.0) {
+            return invalid("penetration", "must be more than 0.0 and less than 1.0");
+        }
+        if self.max_split_hands == 0 {
+            return invalid(
+                "max_split_hands",
+                "must be at least 1 (1 means no splitting)",
+            );
+        }
+        if self.resplit_aces && self.max_split_hands < 3 {
+            return invalid("resplit_aces", "needs max_split_hands of at least 3");
+        }
+        if self.blackjack_payout.paid == 0 || self.blackjack_payout.staked == 0 {
+            return invalid(
+                "blackjack_payout",
+                "both sides of the payout must be more than 0",
+            );
+        }
+        Ok(())
+    }
+
+    /// A shoe made up to these rules, unshuffled.
+    pub fn shoe(&self) -> Shoe {
+        Shoe::new(self.decks, self.penetration)
+    }
+
+    /// Rough house edge, as a percentage of the initial bet, for a player using
+    /// basic strategy. Built up by adding the published effect of each rule to
+    /// the edge of the default six deck game, so it is only good to around a
+    /// tenth of a percent.
+    pub fn house_edge(&self) -> f64 {
+        let mut edge = 0.40;
+
+        edge += match self.decks {
+            1 => -0.48,
+            2 => -0.19,
+            3 => -0.10,
+            4 => -0.06,
+            5 => -0.03,
+            6 => 0.0,
+            7 => 0.01,
+            _ => 0.02,
+        };
+        if self.dealer == DealerRule::HitSoft17 {
+            edge += 0.22;
+        }
+        edge += match self.double {
+            DoubleRule::AnyTwoCards => 0.0,
+            DoubleRule::NineToEleven => 0.09,
+            DoubleRule::TenOrEleven => 0.18,
+            DoubleRule::NotAllowed => 1.60,
+        };
+        if !self.double_after_split && self.double != DoubleRule::NotAllowed {
+            edge += 0.14;
+        }
+        edge += match self.max_split_hands {
+            0 | 1 => 0.57,
+            2 => 0.03,
+            3 => 0.01,
+            _ => 0.0,
+        };
+        if self.resplit_aces {
+            edge -= 0.08;
+        }
+        if !self.one_card_split_aces {
+            edge -= 0.19;
+        }
+        if self.no_hole_card {
+            edge += 0.11;
+        }
+        edge += match self.surrender {
+            SurrenderRule::NotAllowed => 0.0,
+            SurrenderRule::Late => -0.08,
+            SurrenderRule::Early => -0.63,
+        };
+        // About 4.6% of hands are a player natural
+        edge += (1.5 - self.blackjack_payout.as_f64()) * 4.6;
+        edge
+    }
+
+    /// May the player double down on these cards? Only two card hands can be
+    /// doubled, and hands from a split only when double after split is allowed.
+    pub fn can_double(&self, cards: &[Card], from_split: bool) -> bool {
+        cards.len() == 2
+            && (!from_split || self.double_after_split)
+            && self.double.allows(HandValue::of(cards))
+    }
+
+    /// Are these cards a pair that may be split? `hands` is the number of hands
+    /// the player already has and `from_split` whether this hand came from a
+    /// split, which matters for resplitting aces.
+    pub fn can_split(&self, cards: &[Card], from_split: bool, hands: usize) -> bool {
+        if cards.len() != 2 || hands >= self.max_split_hands as usize {
+            return false;
+        }
+        let (first, second) = (cards[0].rank, cards[1].rank);
+        let pair = first == second
+            || (self.split_unlike_tens
+                && first.blackjack_value() == 10
+                && second.blackjack_value() == 10);
+        pair && !(first == Rank::ACE && from_split && !self.resplit_aces)
+    }
+}
+
+// A one line summary, e.g. "6 decks, S17, DAS, double any two cards, ..."
+impl fmt::Display for RuleSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let decks = if self.decks == 1 { "deck" } else { "decks" };
+        write!(
+            f,
+            "{} {}, {}, {}",
+            self.decks, decks, self.dealer, self.double
+        )?;
+        if self.double_after_split
+            && self.double != DoubleRule::NotAllowed
+            && self.max_split_hands > 1
+        {
+            write!(f, ", DAS")?;
+        }
+        write!(f, ", split to {} hands", self.max_split_hands)?;
+        if self.resplit_aces {
+            write!(f, ", RSA")?;
+        }
+        if !self.one_card_split_aces {
+            write!(f, ", hit split aces")?;
+        }
+        if self.no_hole_card {
+            write!(f, ", no hole card")?;
+        }
+        write!(
+            f,
+            ", {}, blackjack pays {}",
+            self.surrender, self.blackjack_payout
+        )
+    }
+}
+
+//@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@
+// Tests
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::Suit;
+
+    #[test]
+    fn soft_17_depends_on_rule() {
+        let soft_17 = HandValue {
+            total: 17,
+            soft: true,
+            blackjack: false,
+        };
+        assert!(!DealerRule::StandSoft17.should_hit(soft_17));
+        assert!(DealerRule::HitSoft17.should_hit(soft_17));
+    }
+
+    #[test]
+    fn hard_17_always_stands() {
+        let hard_17 = HandValue {
+            total: 17,
+            soft: false,
+            blackjack: false,
+        };
+        assert!(!DealerRule::StandSoft17.should_hit(hard_17));
+        assert!(!DealerRule::HitSoft17.should_hit(hard_17));
+    }
+
+    #[test]
+    fn double_restrictions() {
+        let eleven = HandValue {
+            total: 11,
+            soft: false,
+            blackjack: false,
+        };
+        let nine = HandValue {
+            total: 9,
+            soft: false,
+            blackjack: false,
+        };
+        assert!(DoubleRule::NineToEleven.allows(nine));
+        assert!(!DoubleRule::TenOrEleven.allows(nine));
+        assert!(DoubleRule::TenOrEleven.allows(eleven));
+        assert!(!DoubleRule::NotAllowed.allows(eleven));
+    }
+
+    #[test]
+    fn pairs_that_can_be_split() {
+        let rules = RuleSet::default();
+        let aces = [
+            Card::new(Rank::ACE, Suit::HEARTS),
+            Card::new(Rank::ACE, Suit::CLUBS),
+        ];
+        let jack_queen = [
+            Card::new(Rank::JACK, Suit::HEARTS),
+            Card::new(Rank::QUEEN, Suit::CLUBS),
+        ];
+        assert!(rules.can_split(&aces, false, 1));
+        assert!(!rules.can_split(&aces, true, 2));
+        assert!(!rules.can_split(&aces, false, 4));
+        assert!(rules.can_split(&jack_queen, false, 1));
+
+        let strict = RuleSet {
+            resplit_aces: true,
+            split_unlike_tens: false,
+            ..RuleSet::default()
+        };
+        assert!(strict.can_split(&aces, true, 2));
+        assert!(!strict.can_split(&jack_queen, false, 1));
+    }
+
+    #[test]
+    fn presets_by_name() {
+        for name in RuleSet::PRESETS {
+            assert!(RuleSet::preset(name).is_some(), "{}", name);
+        }
+        assert!(RuleSet::preset("monte-carlo").is_none());
+        assert!(RuleSet::preset("european").unwrap().no_hole_card);
+    }
+
+    #[test]
+    fn house_edge_follows_rules() {
+        let default = RuleSet::default().house_edge();
+        assert!((default - 0.40).abs() < 1e-9);
+        assert!(RuleSet::vegas_strip().house_edge() < default);
+        // 6:5 costs the player far more than a single deck saves
+        assert!(RuleSet::single_deck_six_to_five().house_edge() > 1.0);
+    }
+
+    #[test]
+    fn toml_round_trip() {
+        for name in RuleSet::PRESETS {
+            let rules = RuleSet::preset(name).unwrap();
+            assert_eq!(RuleSet::from_toml(&rules.to_toml()).unwrap(), rules);
+        }
+    }
+
+    #[test]
+    fn toml_only_needs_changed_keys() {
+        let rules = RuleSet::from_toml("decks = 2\ndealer = \"H17\"\nblackjack_payout = \"6:5\"\n")
+            .unwrap();
+        assert_eq!(rules.decks, 2);
+        assert_eq!(rules.dealer, DealerRule::HitSoft17);
+        assert_eq!(rules.blackjack_payout, Payout::SIX_TO_FIVE);
+        assert_eq!(rules.surrender, RuleSet::default().surrender);
+    }
+
+    #[test]
+    fn toml_errors_name_the_key() {
+        let error = RuleSet::from_toml("dekcs = 6\n").unwrap_err().to_string();
+        assert!(error.contains("dekcs"), "{}", error);
+
+        let error = RuleSet::from_toml("decks = 0\n").unwrap_err().to_string();
+        assert!(error.contains("`decks`"), "{}", error);
+
+        let error = RuleSet::from_toml("surrender = \"sometimes\"\n")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("surrender"), "{}", error);
+
+        let error = RuleSet::from_toml("resplit_aces = true\nmax_split_hands = 2\n")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("`resplit_aces`"), "{}", error);
+
+        let error = RuleSet::from_toml("penetration = 1.0\n")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("`penetration`"), "{}", error);
+        assert!(RuleSet::from_toml("penetration = 0.95\n").is_ok());
+    }
+
+    #[test]
+    fn double_after_split_is_ignored_without_doubling_or_splitting() {
+        let rules = RuleSet::from_toml("double = \"none\"\n").unwrap();
+        assert_eq!(rules.double, DoubleRule::NotAllowed);
+        assert!(rules.double_after_split);
+
+        let rules = RuleSet::from_toml("max_split_hands = 1\n").unwrap();
+        assert_eq!(rules.max_split_hands, 1);
+    }
+}
@@ -0,0 +1,141 @@
+//%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+// A dealing shoe holding several decks of cards
+//
+use crate::card::{Card, Cards};
+use crate::deck::{CardSource, Deck};
+use rand::prelude::SliceRandom;
+use rand::{thread_rng, Rng};
+
+//#############################################################################
+// A shoe of one or more decks with a cut card
+//
+// The cut card is placed so that the given fraction of the shoe (the
+// penetration) is dealt before a reshuffle is due. Dealing carries on past
+// the cut card until the end of the round, so it is up to the caller to
+// check needs_reshuffle() between rounds.
+//
+#[derive(Debug)]
+pub struct Shoe {
+    decks: u8,
+    cards: Cards,
+    cut_card: usize,
+}
+
+impl Shoe {
+    /// Create an unshuffled shoe of the given number of decks with the cut card
+    /// placed after `penetration` (0.0 to 1.0) of the cards.
+    ///
+    /// # Panics
+    ///
+    /// If there are no decks or the penetration is not between 0.0 and 1.0.
+    pub fn new(decks: u8, penetration: f64) -> Self {
+        assert!(decks > 0, "a shoe needs at least one deck");
+        assert!(
+            (0.0..=1.0).contains(&penetration),
+            "penetration must be between 0.0 and 1.0"
+        );
+
+        let total = decks as usize * 52;
+        let dealt = (total as f64 * penetration).round() as usize;
+        let mut shoe = Self {
+            decks,
+            cards: Cards::new(),
+            cut_card: total - dealt,
+        };
+        shoe.refill();
+        shoe
+    }
+
+    /// Number of decks the shoe was built from.
+    pub fn decks(&self) -> u8 {
+        self.decks
+    }
+
+    /// The cards left in the shoe.
+    pub fn cards(&self) -> &Cards {
+        &self.cards
+    }
+
+    pub fn number_of_cards(&self) -> usize {
+        self.cards.len()
+    }
+
+    /// Number of decks still to be dealt, as used when working out a true count.
+    pub fn decks_remaining(&self) -> f64 {
+        self.cards.len() as f64 / 52.0
+    }
+
+    /// Has the cut card come out?
+    pub fn needs_reshuffle(&self) -> bool {
+        self.cards.len() <= self.cut_card
+    }
+
+    pub fn shuffle(&mut self) {
+        self.shuffle_with(&mut thread_rng());
+    }
+
+    /// Shuffle using the given random number generator, see Deck::shuffle_with().
+    pub fn shuffle_with<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        self.cards.shuffle(rng);
+    }
+
+    /// Put all of the dealt cards back into the shoe and shuffle it.
+    pub fn reshuffle(&mut self) {
+        self.reshuffle_with(&mut thread_rng());
+    }
+
+    /// Put all of the dealt cards back into the shoe and shuffle it using the
+    /// given random number generator.
+    pub fn reshuffle_with<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        self.refill();
+        self.shuffle_with(rng);
+    }
+
+    pub fn draw_card(&mut self) -> Option<Card> {
+        self.cards.pop()
+    }
+
+    fn refill(&mut self) {
+        self.cards.clear();
+        for _ in 0..self.decks {
+            self.cards.extend(Deck::new().cards().iter());
+        }
+    }
+}
+
+impl CardSource for Shoe {
+    fn draw_card(&mut self) -> Option<Card> {
+        Shoe::draw_card(self)
+    }
+}
+
+//@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@
+// Tests
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eight_deck_shoe_has_416_cards() {
+        let shoe = Shoe::new(8, 0.75);
+        assert_eq!(shoe.number_of_cards(), 416);
+        assert_eq!(shoe.decks_remaining(), 8.0);
+    }
+
+    #[test]
+    fn reshuffle_due_at_cut_card() {
+        // 75% of 104 cards is 78
+        let mut shoe = Shoe::new(2, 0.75);
+        for _ in 0..77 {
+            shoe.draw_card();
+        }
+        assert!(!shoe.needs_reshuffle());
+        shoe.draw_card();
+        assert!(shoe.needs_reshuffle());
+
+        shoe.reshuffle();
+        assert!(!shoe.needs_reshuffle());
+        assert_eq!(shoe.number_of_cards(), 104);
+    }
+}
@@ -0,0 +1,1079 @@
+//%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+// Playing a lot of rounds headless to measure how a strategy does
+//
+use crate::betting::BetRamp;
+use crate::card::Card;
+use crate::counting::{Counter, CountingSystem};
+use crate::deck::CardSource;
+use crate::deviations::{DeviationStrategy, DeviationTable};
+use crate::game::{Outcome, Round};
+use crate::money::Money;
+use crate::rules::RuleSet;
+use crate::shoe::Shoe;
+use crate::strategy::Strategy;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::cell::Cell;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// How many standard errors either side of the EV a 95% confidence interval
+/// reaches.
+const CONFIDENCE_95: f64 = 1.96;
+
+/// The same as CONFIDENCE_95 for the mean of a few samples, by Student's t
+/// for 1 to 30 degrees of freedom.
+const T_95: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+    2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+    2.052, 2.048, 2.045, 2.042,
+];
+
+/// Deviation gains are compared over at least this many chunks.
+const GAIN_CHUNKS: u64 = 30;
+
+//#############################################################################
+// What happened over a run of rounds
+//
+// Results are added up exactly as whole numbers of a small unit, so stats
+// from separate runs can be merged in any order and give the same answer.
+//
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SimulationStats {
+    /// Rounds played, each starting with one initial bet.
+    pub rounds: u64,
+    /// Rounds dealt while the player was wonged out and not betting.
+    pub rounds_sat_out: u64,
+    /// Hands played, which is more than the rounds when hands are split.
+    pub hands: u64,
+    pub wins: u64,
+    pub losses: u64,
+    pub pushes: u64,
+    pub blackjacks: u64,
+    pub even_money: u64,
+    pub surrenders: u64,
+    // Sums of each round's net result and its square, in units of the
+    // initial bet divided by `per_bet`
+    per_bet: i64,
+    wagered: i128,
+    net: i128,
+    net_squared: i128,
+    // Sums of the true count at the start of each round in hundredths, its
+    // square and its product with the net result
+    count: i128,
+    count_squared: i128,
+    count_net: i128,
+}
+
+impl SimulationStats {
+    /// Empty stats for results measured in `per_bet` units per initial bet.
+    fn new(per_bet: i64) -> Self {
+        Self {
+            per_bet,
+            ..Self::default()
+        }
+    }
+
+    /// Add in a finished round, which started at the true count in
+    /// hundredths.
+    fn record(&mut self, round: &Round, true_count: i64) {
+        let net = round
+            .net()
+            .whole_cents()
+            .expect("the bet is chosen so that results are whole cents") as i128;
+        self.rounds += 1;
+        self.wagered += round.initial_bet().whole_cents().unwrap() as i128;
+        self.net += net;
+        self.net_squared += net * net;
+        let true_count = true_count as i128;
+        self.count += true_count;
+        self.count_squared += true_count * true_count;
+        self.count_net += true_count * net;
+        for outcome in round.outcomes() {
+            self.hands += 1;
+            match outcome {
+                Outcome::Win => self.wins += 1,
+                Outcome::Lose => self.losses += 1,
+                Outcome::Push => self.pushes += 1,
+                Outcome::Blackjack => self.blackjacks += 1,
+                Outcome::EvenMoney => self.even_money += 1,
+                Outcome::Surrendered => self.surrenders += 1,
+            }
+        }
+    }
+
+    /// Add in the stats of another run.
+    ///
+    /// # Panics
+    ///
+    /// If the runs measured their results in different units.
+    pub fn merge(&mut self, other: &SimulationStats) {
+        if other.per_bet == 0 {
+            return;
+        }
+        if self.per_bet == 0 {
+            self.per_bet = other.per_bet;
+        }
+        assert_eq!(self.per_bet, other.per_bet, "stats use different units");
+        self.rounds += other.rounds;
+        self.rounds_sat_out += other.rounds_sat_out;
+        self.hands += other.hands;
+        self.wins += other.wins;
+        self.losses += other.losses;
+        self.pushes += other.pushes;
+        self.blackjacks += other.blackjacks;
+        self.even_money += other.even_money;
+        self.surrenders += other.surrenders;
+        self.wagered += other.wagered;
+        self.net += other.net;
+        self.net_squared += other.net_squared;
+        self.count += other.count;
+        self.count_squared += other.count_squared;
+        self.count_net += other.count_net;
+    }
+
+    /// The average result of a round played, in betting units. With a flat
+    /// bet of one unit this is per initial bet.
+    pub fn ev(&self) -> f64 {
+        if self.rounds == 0 {
+            return 0.0;
+        }
+        self.net as f64 / self.per_bet as f64 / self.rounds as f64
+    }
+
+    /// The variance of a round's result, in betting units squared. Doubles,
+    /// splits and insurance all make it bigger than that of a single bet.
+    pub fn variance(&self) -> f64 {
+        if self.rounds < 2 {
+            return 0.0;
+        }
+        let n = self.rounds as f64;
+        let unit = self.per_bet as f64;
+        let mean = self.net as f64 / unit / n;
+        let mean_square = self.net_squared as f64 / (unit * unit) / n;
+        (mean_square - mean * mean) * n / (n - 1.0)
+    }
+
+    /// The standard deviation of a round's result, in betting units.
+    pub fn standard_deviation(&self) -> f64 {
+        self.variance().sqrt()
+    }
+
+    /// The standard error of the EV, how far it is likely to be from the
+    /// true EV after this many rounds.
+    pub fn standard_error(&self) -> f64 {
+        if self.rounds == 0 {
+            return 0.0;
+        }
+        self.standard_deviation() / (self.rounds as f64).sqrt()
+    }
+
+    /// The range the true EV lies in with 95% confidence.
+    pub fn confidence_interval(&self) -> (f64, f64) {
+        let half_width = CONFIDENCE_95 * self.standard_error();
+        (self.ev() - half_width, self.ev() + half_width)
+    }
+
+    /// The average initial bet of a round played, in betting units.
+    pub fn average_bet(&self) -> f64 {
+        if self.rounds == 0 {
+            return 0.0;
+        }
+        self.wagered as f64 / self.per_bet as f64 / self.rounds as f64
+    }
+
+    /// The result per unit of initial bets, the player's edge.
+    pub fn ev_per_unit_bet(&self) -> f64 {
+        if self.wagered == 0 {
+            return 0.0;
+        }
+        self.net as f64 / self.wagered as f64
+    }
+
+    /// The expected win in betting units over an hour at the table, counting
+    /// the rounds sat out as taking time too.
+    pub fn hourly_ev(&self, rounds_per_hour: f64) -> f64 {
+        let dealt = self.rounds + self.rounds_sat_out;
+        if dealt == 0 {
+            return 0.0;
+        }
+        self.net as f64 / self.per_bet as f64 / dealt as f64 * rounds_per_hour
+    }
+
+    /// The standard deviation of an hour's result in betting units.
+    pub fn hourly_standard_deviation(&self, rounds_per_hour: f64) -> f64 {
+        let dealt = self.rounds + self.rounds_sat_out;
+        if dealt < 2 {
+            return 0.0;
+        }
+        let n = dealt as f64;
+        let unit = self.per_bet as f64;
+        let mean = self.net as f64 / unit / n;
+        let mean_square = self.net_squared as f64 / (unit * unit) / n;
+        ((mean_square - mean * mean) * n / (n - 1.0) * rounds_per_hour).sqrt()
+    }
+
+    /// The covariance of a round's result with the true count at the start
+    /// of the round, in initial bets times true counts.
+    pub fn count_covariance(&self) -> f64 {
+        self.count_covariance_with(self.count_net, self.net, self.per_bet as f64 * 100.0)
+    }
+
+    /// How much the EV goes up for each point of true count, from a straight
+    /// line fitted through the results.
+    pub fn ev_per_true_count(&self) -> f64 {
+        let count_variance =
+            self.count_covariance_with(self.count_squared, self.count, 100.0 * 100.0);
+        if count_variance == 0.0 {
+            return 0.0;
+        }
+        self.count_covariance() / count_variance
+    }
+
+    // The sample covariance of the true count with something else, given the
+    // sum of their products and the sum of the other, scaled down by `unit`
+    fn count_covariance_with(&self, products: i128, sum: i128, unit: f64) -> f64 {
+        if self.rounds < 2 {
+            return 0.0;
+        }
+        let n = self.rounds as i128;
+        // Worked out in whole numbers so as not to lose the small difference
+        let difference = n * products - self.count * sum;
+        difference as f64 / unit / (n * (n - 1)) as f64
+    }
+
+    /// How often hands ended with a count, as a fraction of the hands.
+    pub fn frequency(&self, count: u64) -> f64 {
+        if self.hands == 0 {
+            return 0.0;
+        }
+        count as f64 / self.hands as f64
+    }
+}
+
+// A short report, one figure per line
+impl fmt::Display for SimulationStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let percent = |count| self.frequency(count) * 100.0;
+        write!(f, "Rounds: {} ({} hands)", self.rounds, self.hands)?;
+        if self.rounds_sat_out > 0 {
+            write!(f, ", {} sat out", self.rounds_sat_out)?;
+        }
+        writeln!(f)?;
+        writeln!(
+            f,
+            "EV per initial bet: {:+.4}%",
+            self.ev_per_unit_bet() * 100.0
+        )?;
+        if self.wagered != self.per_bet as i128 * self.rounds as i128 {
+            writeln!(
+                f,
+                "Win rate: {:+.4} units per round played, average bet {:.2} units",
+                self.ev(),
+                self.average_bet()
+            )?;
+        }
+        let (low, high) = self.confidence_interval();
+        writeln!(
+            f,
+            "Standard error: {:.4}%, 95% confidence interval: {:+.4}% to {:+.4}%",
+            self.standard_error() * 100.0,
+            low * 100.0,
+            high * 100.0
+        )?;
+        writeln!(
+            f,
+            "Variance per round: {:.4}, standard deviation: {:.4}",
+            self.variance(),
+            self.standard_deviation()
+        )?;
+        writeln!(
+            f,
+            "Covariance with the true count: {:.4} ({:+.4}% EV per true count)",
+            self.count_covariance(),
+            self.ev_per_true_count() * 100.0
+        )?;
+        write!(
+            f,
+            "Wins: {:.2}%, losses: {:.2}%, pushes: {:.2}%, blackjacks: {:.2}%",
+            percent(self.wins),
+            percent(self.losses),
+            percent(self.pushes),
+            percent(self.blackjacks)
+        )?;
+        if self.even_money > 0 {
+            write!(f, ", even money: {:.2}%", percent(self.even_money))?;
+        }
+        if self.surrenders > 0 {
+            write!(f, ", surrenders: {:.2}%", percent(self.surrenders))?;
+        }
+        Ok(())
+    }
+}
+
+//#############################################################################
+// Play rounds from a shoe with a strategy
+//
+// The shoe is shuffled at the start and again whenever the cut card comes
+// out between rounds. Every round is played with the same initial bet, and
+// the true count is kept with Hi-Lo.
+//
+pub fn simulate<R: Rng + ?Sized>(
+    rules: &RuleSet,
+    strategy: &(impl Strategy + ?Sized),
+    rounds: u64,
+    rng: &mut R,
+) -> SimulationStats {
+    let config = SimulationConfig {
+        rounds,
+        threads: 1,
+        ..SimulationConfig::default()
+    };
+    play_rounds(rules, strategy, &config, rounds, rng)
+}
+
+// Play rounds with the counting system and bet ramp of the config, adding up
+// those the player bet on.
+fn play_rounds<R: Rng + ?Sized>(
+    rules: &RuleSet,
+    strategy: &(impl Strategy + ?Sized),
+    config: &SimulationConfig,
+    rounds: u64,
+    rng: &mut R,
+) -> SimulationStats {
+    let mut stats = SimulationStats::new(unit_cents(rules));
+    let afford = |_| true;
+    deal_rounds(
+        rules,
+        strategy,
+        config,
+        rounds,
+        rng,
+        afford,
+        |round, true_count| {
+            match true_count {
+                Some(true_count) => stats.record(round, (true_count * 100.0).round() as i64),
+                None => stats.rounds_sat_out += 1,
+            }
+            true
+        },
+    );
+    stats
+}
+
+// A unit this size makes every payout and half bet a whole number of cents
+fn unit_cents(rules: &RuleSet) -> i64 {
+    200 * rules.blackjack_payout.staked as i64
+}
+
+// Deal rounds from a freshly shuffled shoe with the counting system and bet
+// ramp of the config, handing each to `after` with the true count it was bet
+// at, or None if the player sat it out. Rounds sat out are still played to
+// use up the cards, as other players would. Stops early before a bet that
+// `afford` says the player can't make, or once `after` says not to go on.
+fn deal_rounds<R: Rng + ?Sized>(
+    rules: &RuleSet,
+    strategy: &(impl Strategy + ?Sized),
+    config: &SimulationConfig,
+    rounds: u64,
+    rng: &mut R,
+    mut afford: impl FnMut(Money) -> bool,
+    mut after: impl FnMut(&Round, Option<f64>) -> bool,
+) {
+    let per_bet = unit_cents(rules);
+    let betting = &config.betting;
+
+    let mut shoe = rules.shoe();
+    shoe.shuffle_with(rng);
+    let mut dealing = Dealing {
+        counter: config.counting.counter(rules.decks),
+        shoe: &mut shoe,
+        rng,
+    };
+    let mut seated = betting.seated_at_shuffle();
+    for _ in 0..rounds {
+        if dealing.shoe.needs_reshuffle() {
+            dealing.reshuffle();
+            seated = betting.seated_at_shuffle();
+        }
+        let true_count = dealing.counter.true_count();
+        seated = betting.seated(seated, true_count);
+        let units = if seated { betting.units(true_count) } else { 1 };
+        let bet = Money::cents(per_bet * units as i64);
+        if seated && !afford(bet) {
+            break;
+        }
+        let mut round = Round::deal(&mut dealing, rules, bet);
+        round.play_counting(strategy, &mut dealing, Dealing::seen_true_count);
+        if !after(&round, seated.then_some(true_count)) {
+            break;
+        }
+    }
+}
+
+//#############################################################################
+// How to split a big simulation up
+//
+// The rounds are played in chunks, each from a freshly shuffled shoe with its
+// own stream of random numbers from the seed. Which rounds go in which chunk
+// depends only on the number of rounds and the chunk size, so the result for
+// a seed is the same however many threads share the chunks out.
+//
+// With a precision the run stops early, after the first chunk that brings
+// the 95% confidence interval's half width down to it. Chunks are added up in
+// order before checking, so where it stops doesn't depend on the threads
+// either.
+//
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimulationConfig {
+    /// The most rounds to deal, whether played or sat out.
+    pub rounds: u64,
+    pub seed: u64,
+    pub threads: usize,
+    pub chunk_rounds: u64,
+    /// Stop once the EV is known to within this, in betting units.
+    pub precision: Option<f64>,
+    /// How the true count is kept.
+    pub counting: CountingSystem,
+    /// How many units to bet at each count, and when to sit out.
+    pub betting: BetRamp,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            rounds: 1_000_000,
+            seed: 0,
+            threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+            chunk_rounds: 100_000,
+            precision: None,
+            counting: CountingSystem::default(),
+            betting: BetRamp::flat(),
+        }
+    }
+}
+
+impl SimulationConfig {
+    fn chunks(&self) -> u64 {
+        self.rounds.div_ceil(self.chunk_rounds.max(1))
+    }
+
+    fn chunk_size(&self, chunk: u64) -> u64 {
+        let chunk_rounds = self.chunk_rounds.max(1);
+        chunk_rounds.min(self.rounds - chunk * chunk_rounds)
+    }
+
+    /// The random numbers for a chunk, a separate stream from the seed.
+    fn chunk_rng(&self, chunk: u64) -> ChaCha8Rng {
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+        rng.set_stream(chunk);
+        rng
+    }
+
+    /// The random numbers for a trip, from the top half of the streams so
+    /// that no trip deals the same cards as a chunk of a simulation with the
+    /// same seed.
+    fn trip_rng(&self, trip: u64) -> ChaCha8Rng {
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+        rng.set_stream(1 << 63 | trip);
+        rng
+    }
+
+    fn precise_enough(&self, stats: &SimulationStats) -> bool {
+        self.precision.is_some_and(|precision| {
+            let (low, high) = stats.confidence_interval();
+            stats.rounds > 1 && (high - low) / 2.0 <= precision
+        })
+    }
+}
+
+//#############################################################################
+// Play the rounds on several threads and add up the results
+//
+pub fn simulate_parallel(
+    rules: &RuleSet,
+    strategy: &(impl Strategy + Sync + ?Sized),
+    config: &SimulationConfig,
+) -> SimulationStats {
+    simulate_with_progress(rules, strategy, config, |_| {})
+}
+
+/// As simulate_parallel(), calling `progress` with the results so far after
+/// each chunk, which is handy for watching the EV converge.
+pub fn simulate_with_progress(
+    rules: &RuleSet,
+    strategy: &(impl Strategy + Sync + ?Sized),
+    config: &SimulationConfig,
+    progress: impl FnMut(&SimulationStats) + Send,
+) -> SimulationStats {
+    let chunks = config.chunks();
+    let next_chunk = AtomicU64::new(0);
+    let merging = Mutex::new(Merging {
+        finished: vec![None; chunks as usize],
+        merged: 0,
+        total: SimulationStats::default(),
+        done: chunks == 0,
+        progress,
+    });
+
+    thread::scope(|scope| {
+        for _ in 0..config.threads.clamp(1, chunks.max(1) as usize) {
+            scope.spawn(|| loop {
+                let chunk = next_chunk.fetch_add(1, Ordering::Relaxed);
+                if chunk >= chunks || merging.lock().unwrap().done {
+                    break;
+                }
+                let stats = play_rounds(
+                    rules,
+                    strategy,
+                    config,
+                    config.chunk_size(chunk),
+                    &mut config.chunk_rng(chunk),
+                );
+
+                merging.lock().unwrap().finish(chunk, stats, config);
+            });
+        }
+    });
+
+    merging.into_inner().unwrap().total
+}
+
+// The chunks finished so far, added up in order
+struct Merging<F> {
+    finished: Vec<Option<SimulationStats>>,
+    merged: usize,
+    total: SimulationStats,
+    done: bool,
+    progress: F,
+}
+
+impl<F: FnMut(&SimulationStats)> Merging<F> {
+    /// Keep a finished chunk and add in any that are now next in order.
+    fn finish(&mut self, chunk: u64, stats: SimulationStats, config: &SimulationConfig) {
+        self.finished[chunk as usize] = Some(stats);
+        while !self.done {
+            let Some(stats) = self.finished.get_mut(self.merged).and_then(Option::take) else {
+                break;
+            };
+            self.total.merge(&stats);
+            self.merged += 1;
+            (self.progress)(&self.total);
+            self.done = self.merged == self.finished.len() || config.precise_enough(&self.total);
+        }
+    }
+}
+
+//#############################################################################
+// How much EV each deviation in a table is worth
+//
+// Each deviation is left out in turn and the same rounds are played again
+// from the same seed. Its worth is what the EV drops by without it, in
+// betting units per round. Playing from the same shoes makes the difference
+// far more accurate than two separate runs would be, but a deviation changes
+// how the rest of the shoe is dealt so it is still noisy, more so with a bet
+// ramp. The standard error comes from how the difference varies between
+// chunks, so the rounds are dealt in smaller chunks than usual if that is
+// what it takes to have at least 30. Every run plays all of the rounds, as
+// stopping at a precision would compare different numbers of rounds.
+//
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DeviationGain {
+    pub gain: f64,
+    /// NaN with fewer than two chunks to compare.
+    pub standard_error: f64,
+    /// The chunks compared.
+    pub chunks: u64,
+}
+
+impl DeviationGain {
+    /// How far either side of the gain its 95% confidence interval reaches,
+    /// if there are enough chunks to tell.
+    pub fn half_width(&self) -> Option<f64> {
+        let degrees = self.chunks.checked_sub(1).filter(|degrees| *degrees > 0)?;
+        let t = T_95
+            .get(degrees as usize - 1)
+            .copied()
+            .unwrap_or(CONFIDENCE_95);
+        Some(t * self.standard_error)
+    }
+}
+
+pub fn deviation_gains(
+    rules: &RuleSet,
+    strategy: &(impl Strategy + Sync + ?Sized),
+    deviations: &DeviationTable,
+    config: &SimulationConfig,
+) -> (SimulationStats, Vec<DeviationGain>) {
+    let config = SimulationConfig {
+        precision: None,
+        chunk_rounds: config
+            .chunk_rounds
+            .min(config.rounds.div_ceil(GAIN_CHUNKS))
+            .max(1),
+        ..config.clone()
+    };
+    // The EV of each chunk as well as the whole run
+    let run = |deviations: DeviationTable| {
+        let strategy = DeviationStrategy::new(strategy, deviations);
+        let mut chunks = Vec::new();
+        let mut before = (0.0, 0);
+        let stats = simulate_with_progress(rules, &strategy, &config, |so_far| {
+            let net = so_far.ev() * so_far.rounds as f64;
+            let rounds = so_far.rounds - before.1;
+            chunks.push(if rounds == 0 {
+                0.0
+            } else {
+                (net - before.0) / rounds as f64
+            });
+            before = (net, so_far.rounds);
+        });
+        (stats, chunks)
+    };
+
+    let (all, all_chunks) = run(deviations.clone());
+    let gains = (0..deviations.len())
+        .map(|index| {
+            let (without, chunks) = run(deviations.without(index));
+            let differences: Vec<f64> = all_chunks
+                .iter()
+                .zip(&chunks)
+                .map(|(all, without)| all - without)
+                .collect();
+            let n = differences.len() as f64;
+            let mean = differences.iter().sum::<f64>() / n;
+            let variance = differences
+                .iter()
+                .map(|difference| (difference - mean).powi(2))
+                .sum::<f64>()
+                / (n - 1.0);
+            DeviationGain {
+                gain: all.ev() - without.ev(),
+                standard_error: if n < 2.0 {
+                    f64::NAN
+                } else {
+                    (variance / n).sqrt()
+                },
+                chunks: differences.len() as u64,
+            }
+        })
+        .collect();
+    (all, gains)
+}
+
+//#############################################################################
+// Trips with a finite bankroll
+//
+// Each trip starts from a fresh shoe with the bankroll and plays the config's
+// rounds, or until what is left can't cover the bet the ramp calls for. That
+// checks the risk of ruin worked out from the win rate, which assumes the
+// results are a smooth random walk, against real hands with their doubles,
+// splits and bet changes. Each trip is dealt from its own stream of the seed,
+// so the result doesn't depend on the threads.
+//
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TripStats {
+    pub trips: u64,
+    /// Trips that lost the bankroll, or so much of it that the next bet
+    /// couldn't be covered.
+    pub ruined: u64,
+}
+
+impl TripStats {
+    /// The share of trips ruined.
+    pub fn risk_of_ruin(&self) -> f64 {
+        if self.trips == 0 {
+            return 0.0;
+        }
+        self.ruined as f64 / self.trips as f64
+    }
+
+    /// The standard error of the risk of ruin.
+    pub fn standard_error(&self) -> f64 {
+        if self.trips == 0 {
+            return 0.0;
+        }
+        let risk = self.risk_of_ruin();
+        (risk * (1.0 - risk) / self.trips as f64).sqrt()
+    }
+}
+
+/// Play trips of `config.rounds` rounds dealt, each with a bankroll of the
+/// given betting units.
+pub fn simulate_trips(
+    rules: &RuleSet,
+    strategy: &(impl Strategy + Sync + ?Sized),
+    config: &SimulationConfig,
+    bankroll: f64,
+    trips: u64,
+) -> TripStats {
+    let bankroll = (bankroll * unit_cents(rules) as f64).round() as i64;
+    let next_trip = AtomicU64::new(0);
+    let total = Mutex::new(TripStats::default());
+    thread::scope(|scope| {
+        for _ in 0..config.threads.clamp(1, trips.max(1) as usize) {
+            scope.spawn(|| {
+                let mut stats = TripStats::default();
+                loop {
+                    let trip = next_trip.fetch_add(1, Ordering::Relaxed);
+                    if trip >= trips {
+                        break;
+                    }
+                    let left = Cell::new(bankroll);
+                    let short = Cell::new(false);
+                    let afford = |bet: Money| {
+                        short.set(bet.whole_cents().unwrap() > left.get());
+                        !short.get()
+                    };
+                    let rng = &mut config.trip_rng(trip);
+                    deal_rounds(
+                        rules,
+                        strategy,
+                        config,
+                        config.rounds,
+                        rng,
+                        afford,
+                        |round, bet| {
+                            // Rounds sat out aren't bet on
+                            if bet.is_some() {
+                                let net = round
+                                    .net()
+                                    .whole_cents()
+                                    .expect("the bet is chosen so that results are whole cents");
+                                left.set(left.get() + net);
+                            }
+                            left.get() > 0
+                        },
+                    );
+                    stats.trips += 1;
+                    stats.ruined += u64::from(short.get() || left.get() <= 0);
+                }
+                let mut total = total.lock().unwrap();
+                total.trips += stats.trips;
+                total.ruined += stats.ruined;
+            });
+        }
+    });
+    total.into_inner().unwrap()
+}
+
+// Deals from the shoe, reshuffling it should it run out part way through a
+// round. That can only happen with the cut card very near the end, and the
+// cards on the table going back in makes no real difference. Counts the
+// cards dealt since the shuffle.
+struct Dealing<'a, R: Rng + ?Sized> {
+    shoe: &'a mut Shoe,
+    rng: &'a mut R,
+    counter: Counter,
+}
+
+impl<R: Rng + ?Sized> Dealing<'_, R> {
+    fn reshuffle(&mut self) {
+        self.shoe.reshuffle_with(self.rng);
+        self.counter.reset();
+    }
+
+    /// The true count of the cards the player has seen, which leaves out
+    /// the dealer's hole card until it is turned over.
+    fn seen_true_count(&self, round: &Round) -> f64 {
+        match round.dealer().cards().get(1) {
+            Some(hole) if !round.is_finished() => self.counter.without(*hole).true_count(),
+            _ => self.counter.true_count(),
+        }
+    }
+}
+
+impl<R: Rng + ?Sized> CardSource for Dealing<'_, R> {
+    fn draw_card(&mut self) -> Option<Card> {
+        if self.shoe.number_of_cards() == 0 {
+            self.reshuffle();
+        }
+        let card = self.shoe.draw_card()?;
+        self.counter.count(card);
+        Some(card)
+    }
+}
+
+//@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@
+// Tests
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::risk::WinRate;
+    use crate::strategy::BasicStrategy;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    fn run(rules: &RuleSet, rounds: u64, seed: u64) -> SimulationStats {
+        let strategy = BasicStrategy::new(rules);
+        simulate(
+            rules,
+            &strategy,
+            rounds,
+            &mut ChaCha8Rng::seed_from_u64(seed),
+        )
+    }
+
+    #[test]
+    fn same_seed_same_stats() {
+        let rules = RuleSet::default();
+        assert_eq!(run(&rules, 2_000, 3), run(&rules, 2_000, 3));
+        assert_ne!(run(&rules, 2_000, 3), run(&rules, 2_000, 4));
+    }
+
+    #[test]
+    fn basic_strategy_is_close_to_even() {
+        let stats = run(&RuleSet::default(), 50_000, 1);
+        assert_eq!(stats.rounds, 50_000);
+        assert_eq!(
+            stats.hands,
+            stats.wins + stats.losses + stats.pushes + stats.blackjacks
+        );
+        assert!(stats.hands > stats.rounds);
+        // Within about three standard errors of the 0.4% house edge
+        assert!(stats.ev().abs() < 0.02, "{}", stats.ev());
+        assert!((stats.standard_deviation() - 1.15).abs() < 0.05);
+        assert!((stats.frequency(stats.blackjacks) - 0.045).abs() < 0.005);
+    }
+
+    #[test]
+    fn merged_runs_add_up() {
+        let rules = RuleSet::default();
+        let (first, second) = (run(&rules, 1_000, 1), run(&rules, 500, 2));
+        let mut merged = first.clone();
+        merged.merge(&second);
+        assert_eq!(merged.rounds, 1_500);
+        let ev = (first.ev() * 1_000.0 + second.ev() * 500.0) / 1_500.0;
+        assert!((merged.ev() - ev).abs() < 1e-12);
+    }
+
+    #[test]
+    fn thread_count_makes_no_difference() {
+        let rules = RuleSet::default();
+        let strategy = BasicStrategy::new(&rules);
+        let config = |threads| SimulationConfig {
+            rounds: 10_500,
+            seed: 9,
+            threads,
+            chunk_rounds: 1_000,
+            precision: None,
+            counting: CountingSystem::default(),
+            betting: BetRamp::flat(),
+        };
+        let one = simulate_parallel(&rules, &strategy, &config(1));
+        assert_eq!(one.rounds, 10_500);
+        assert_eq!(one, simulate_parallel(&rules, &strategy, &config(3)));
+        assert_eq!(one, simulate_parallel(&rules, &strategy, &config(16)));
+    }
+
+    #[test]
+    fn stops_once_precise_enough() {
+        let rules = RuleSet::default();
+        let strategy = BasicStrategy::new(&rules);
+        // About 17,000 rounds are needed for a standard error of 1%
+        let config = |threads| SimulationConfig {
+            rounds: 1_000_000,
+            seed: 2,
+            threads,
+            chunk_rounds: 1_000,
+            precision: Some(0.0196),
+            counting: CountingSystem::default(),
+            betting: BetRamp::flat(),
+        };
+        let mut seen = Vec::new();
+        let stats = simulate_with_progress(&rules, &strategy, &config(1), |so_far| {
+            seen.push(so_far.rounds)
+        });
+        assert!((10_000..30_000).contains(&stats.rounds), "{}", stats.rounds);
+        assert!(stats.standard_error() <= 0.01);
+        assert_eq!(
+            seen,
+            (1..=seen.len() as u64)
+                .map(|chunk| chunk * 1_000)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(stats, simulate_parallel(&rules, &strategy, &config(4)));
+    }
+
+    #[test]
+    fn statistics_of_known_results() {
+        let mut stats = SimulationStats::new(100);
+        let rounds = [(100, 0), (-100, 100), (-100, 200), (200, 300)];
+        for (net, true_count) in rounds {
+            stats.rounds += 1;
+            stats.net += net;
+            stats.net_squared += net * net;
+            stats.count += true_count;
+            stats.count_squared += true_count * true_count;
+            stats.count_net += true_count * net;
+        }
+        assert_eq!(stats.ev(), 0.25);
+        assert!((stats.variance() - 2.25).abs() < 1e-12);
+        assert!((stats.standard_error() - 0.75).abs() < 1e-12);
+        let (low, high) = stats.confidence_interval();
+        assert!((low - (0.25 - 1.47)).abs() < 1e-12 && (high - (0.25 + 1.47)).abs() < 1e-12);
+        // Counts of 0, 1, 2 and 3 against results of 1, -1, -1 and 2
+        assert!((stats.count_covariance() - 0.5).abs() < 1e-12);
+        assert!((stats.ev_per_true_count() - 0.3).abs() < 1e-12);
+    }
+
+    #[test]
+    fn bets_follow_the_ramp() {
+        let rules = RuleSet::default();
+        let strategy = BasicStrategy::new(&rules);
+        let run = |betting| {
+            let config = SimulationConfig {
+                betting,
+                ..SimulationConfig::default()
+            };
+            play_rounds(
+                &rules,
+                &strategy,
+                &config,
+                2_000,
+                &mut ChaCha8Rng::seed_from_u64(6),
+            )
+        };
+
+        // The same cards come out whatever the bet
+        let flat = run(BetRamp::flat());
+        let double = run(BetRamp::new(2, 2, &[]));
+        assert_eq!(double.average_bet(), 2.0);
+        assert_eq!(double.ev(), flat.ev() * 2.0);
+        assert_eq!(double.ev_per_unit_bet(), flat.ev_per_unit_bet());
+        assert_eq!(double.hourly_ev(100.0), flat.ev() * 200.0);
+
+        let ramp = run(BetRamp::new(1, 8, &[(1.0, 2), (2.0, 4), (3.0, 8)]));
+        assert!(ramp.average_bet() > 1.0 && ramp.average_bet() < 8.0);
+
+        // Waiting for a count that never comes sits out every round
+        let never = run(BetRamp {
+            wong_in: Some(100.0),
+            ..BetRamp::flat()
+        });
+        assert_eq!((never.rounds, never.rounds_sat_out), (0, 2_000));
+        assert_eq!(never.hourly_ev(100.0), 0.0);
+
+        let wonged = run(BetRamp {
+            wong_out: Some(0.0),
+            ..BetRamp::flat()
+        });
+        assert_eq!(wonged.rounds + wonged.rounds_sat_out, 2_000);
+        assert!(wonged.rounds_sat_out > 0);
+    }
+
+    #[test]
+    fn gains_of_each_deviation() {
+        let rules = RuleSet::default();
+        let strategy = BasicStrategy::new(&rules);
+        let config = SimulationConfig {
+            rounds: 4_000,
+            seed: 8,
+            chunk_rounds: 1_000,
+            precision: Some(0.5),
+            ..SimulationConfig::default()
+        };
+        let deviations = DeviationTable::illustrious_18();
+        let (all, gains) = deviation_gains(&rules, &strategy, &deviations, &config);
+        assert_eq!(all.rounds, 4_000);
+        assert_eq!(gains.len(), 18);
+        // Dealt in 30 chunks rather than 4 so that the errors mean something
+        let with_deviations = DeviationStrategy::new(&strategy, deviations.clone());
+        let config = SimulationConfig {
+            precision: None,
+            chunk_rounds: 134,
+            ..config
+        };
+        assert_eq!(all, simulate_parallel(&rules, &with_deviations, &config));
+        // Leaving out the last deviation is the same as playing the rest
+        let rest = DeviationStrategy::new(&strategy, deviations.without(17));
+        let without_last = simulate_parallel(&rules, &rest, &config);
+        assert_eq!(gains[17].gain, all.ev() - without_last.ev());
+        assert!(gains.iter().all(|gain| gain.chunks == 30));
+        // Insurance at +3 comes up often enough to make a difference
+        let insurance = gains[0];
+        let half_width = insurance.half_width().unwrap();
+        assert!(
+            half_width > 0.0 && half_width.is_finite(),
+            "{:?}",
+            insurance
+        );
+        assert_eq!(half_width, 2.045 * insurance.standard_error);
+    }
+
+    #[test]
+    fn trips_are_ruined_as_often_as_expected() {
+        let rules = RuleSet::default();
+        let strategy = BasicStrategy::new(&rules);
+        let config = SimulationConfig {
+            rounds: 1_000,
+            threads: 2,
+            seed: 7,
+            ..SimulationConfig::default()
+        };
+        let trips = simulate_trips(&rules, &strategy, &config, 20.0, 300);
+        assert_eq!(trips.trips, 300);
+        let one_thread = SimulationConfig {
+            threads: 1,
+            ..config.clone()
+        };
+        assert_eq!(
+            simulate_trips(&rules, &strategy, &one_thread, 20.0, 300),
+            trips
+        );
+
+        // About 60% of 1,000 rounds with 20 units go broke
+        let expected = WinRate::new(-0.005, 1.15).risk_of_ruin_within(20.0, 1_000.0);
+        assert!(
+            (trips.risk_of_ruin() - expected).abs() < 4.0 * trips.standard_error(),
+            "{} against {}",
+            trips.risk_of_ruin(),
+            expected
+        );
+    }
+
+    #[test]
+    fn trips_are_dealt_apart_from_chunks() {
+        let config = SimulationConfig::default();
+        for i in 0..4 {
+            assert_ne!(
+                config.trip_rng(i).gen::<u64>(),
+                config.chunk_rng(i).gen::<u64>()
+            );
+        }
+    }
+
+    #[test]
+    fn trips_are_ruined_once_the_bet_cant_be_covered() {
+        let rules = RuleSet::default();
+        let strategy = BasicStrategy::new(&rules);
+        let config = SimulationConfig {
+            rounds: 100,
+            threads: 1,
+            betting: BetRamp::new(2, 2, &[]),
+            ..SimulationConfig::default()
+        };
+
+        // Never enough for the first bet of two units
+        let trips = simulate_trips(&rules, &strategy, &config, 1.5, 50);
+        assert_eq!(trips.ruined, 50);
+
+        // Enough for one bet, but not for a losing double or split after it
+        // or for a second bet after a loss, so most trips are soon over
+        let trips = simulate_trips(&rules, &strategy, &config, 2.0, 200);
+        assert!(trips.risk_of_ruin() > 0.9, "{:?}", trips);
+    }
+
+    #[test]
+    fn full_penetration_never_runs_dry() {
+        let rules = RuleSet {
+            decks: 1,
+            penetration: 1.0,
+            ..RuleSet::default()
+        };
+        assert_eq!(run(&rules, 2_000, 5).rounds, 2_000);
+    }
+}
@@ -0,0 +1,385 @@
+//%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%
+// Deciding how to play a hand
+//
+use crate::card::{Card, Cards};
+use crate::chart::{Chart, ChartAction, ChartError};
+use crate::game::Action;
+use crate::rules::{DealerRule, RuleSet, SurrenderRule};
+use std::path::Path;
+
+//#############################################################################
+// Something that decides how to play a hand, such as a bot or a chart
+//
+pub trait Strategy {
+    /// Pick one of the allowed actions for the player's hand against the
+    /// dealer's upcard.
+    fn decide(&self, hand: &Cards, upcard: Card, allowed: &[Action], rules: &RuleSet) -> Action;
+
+    /// Take insurance (or even money with a natural) when the dealer shows an
+    /// ace? Never, unless the strategy says otherwise.
+    fn take_insurance(&self, _hand: &Cards, _rules: &RuleSet) -> bool {
+        false
+    }
+
+    /// As decide(), when the true count of the cards seen is known. Only
+    /// strategies that play differently by the count need to implement it.
+    fn decide_at_count(
+        &self,
+        hand: &Cards,
+        upcard: Card,
+        allowed: &[Action],
+        rules: &RuleSet,
+        _true_count: f64,
+    ) -> Action {
+        self.decide(hand, upcard, allowed, rules)
+    }
+
+    /// As take_insurance(), when the true count of the cards seen is known.
+    fn take_insurance_at_count(&self, hand: &Cards, rules: &RuleSet, _true_count: f64) -> bool {
+        self.take_insurance(hand, rules)
+    }
+}
+
+// So that a strategy can be borrowed to build another on top of it
+impl<S: Strategy + ?Sized> Strategy for &S {
+    fn decide(&self, hand: &Cards, upcard: Card, allowed: &[Action], rules: &RuleSet) -> Action {
+        (**self).decide(hand, upcard, allowed, rules)
+    }
+
+    fn take_insurance(&self, hand: &Cards, rules: &RuleSet) -> bool {
+        (**self).take_insurance(hand, rules)
+    }
+
+    fn decide_at_count(
+        &self,
+        hand: &Cards,
+        upcard: Card,
+        allowed: &[Action],
+        rules: &RuleSet,
+        true_count: f64,
+    ) -> Action {
+        (**self).decide_at_count(hand, upcard, allowed, rules, true_count)
+    }
+
+    fn take_insurance_at_count(&self, hand: &Cards, rules: &RuleSet, true_count: f64) -> bool {
+        (**self).take_insurance_at_count(hand, rules, true_count)
+    }
+}
+
+//#############################################################################
+// Playing from a chart
+//
+#[derive(Clone, Debug)]
+pub struct ChartStrategy {
+    chart: Chart,
+}
+
+impl ChartStrategy {
+    pub fn new(chart: Chart) -> Self {
+        Self { chart }
+    }
+
+    /// Play from a chart saved as CSV.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ChartError> {
+        Chart::load(path).map(Self::new)
+    }
+
+    pub fn chart(&self) -> &Chart {
+        &self.chart
+    }
+}
+
+impl Strategy for ChartStrategy {
+    fn decide(&self, hand: &Cards, upcard: Card, allowed: &[Action], rules: &RuleSet) -> Action {
+        self.chart
+            .lookup(hand, upcard, allowed.contains(&Action::Split))
+            .resolve(allowed, rules)
+    }
+}
+
+//#############################################################################
+// Total-dependent basic strategy for a set of rules
+//
+// Starts from the usual chart for four or more decks with S17 and adjusts it
+// for H17, one and two deck games, surrender and no hole card. Doubling and
+// splitting restrictions are handled by the chart's fall back choices. Never
+// takes insurance.
+//
+#[derive(Clone, Debug)]
+pub struct BasicStrategy {
+    chart: Chart,
+}
+
+impl BasicStrategy {
+    pub fn new(rules: &RuleSet) -> Self {
+        Self {
+            chart: basic_strategy_chart(rules),
+        }
+    }
+
+    pub fn chart(&self) -> &Chart {
+        &self.chart
+    }
+}
+
+impl Strategy for BasicStrategy {
+    fn decide(&self, hand: &Cards, upcard: Card, allowed: &[Action], rules: &RuleSet) -> Action {
+        self.chart
+            .lookup(hand, upcard, allowed.contains(&Action::Split))
+            .resolve(allowed, rules)
+    }
+}
+
+/// Build the basic strategy chart for the given rules.
+pub fn basic_strategy_chart(rules: &RuleSet) -> Chart {
+    use ChartAction::*;
+
+    let h17 = rules.dealer == DealerRule::HitSoft17;
+    let few_decks = rules.decks <= 2;
+    let single_deck = rules.decks == 1;
+    let mut chart = Chart::new();
+
+    for up in 2..=11 {
+        let between = |low, high| (low..=high).contains(&up);
+
+        // Hard totals
+        for total in Chart::HARD_TOTALS {
+            let action = match total {
+                4..=7 => Hit,
+                8 if single_deck && between(5, 6) => DoubleOrHit,
+                8 => Hit,
+                9 if between(3, 6) || (few_decks && up == 2) => DoubleOrHit,
+                9 => Hit,
+                10 if between(2, 9) => DoubleOrHit,
+                10 => Hit,
+                11 if up != 11 || h17 || few_decks => DoubleOrHit,
+                11 => Hit,
+                12 if between(4, 6) => Stand,
+                12 => Hit,
+                13..=16 if between(2, 6) => Stand,
+                13..=16 => Hit,
+                _ => Stand,
+            };
+            chart.set_hard(total, up, action);
+        }
+
+        // Soft totals
+        for total in Chart::SOFT_TOTALS {
+            let action = match total {
+                13 | 14 if between(5, 6) || (single_deck && up == 4) => DoubleOrHit,
+                15 | 16 if between(4, 6) => DoubleOrHit,
+                17 if between(3, 6) => DoubleOrHit,
+                18 if between(3, 6) || (h17 && up == 2) => DoubleOrStand,
+                18 if between(2, 8) => Stand,
+                18 => Hit,
+                19 if up == 6 && (h17 || single_deck) => DoubleOrStand,
+                19..=21 => Stand,
+                _ => Hit,
+            };
+            chart.set_soft(total, up, action);
+        }
+
+        // Pairs, by the value of one card
+        for card in Chart::PAIR_VALUES {
+            let action = match card {
+                2 | 3 if between(2, 3) => SplitIfDasOrHit,
+                2 | 3 if between(4, 7) => Split,
+                4 if between(5, 6) => SplitIfDasOrHit,
+                6 if up == 2 => SplitIfDasOrHit,
+                6 if between(3, 6) => Split,
+                7 if between(2, 7) => Split,
+                8 | 11 => Split,
+                9 if between(2, 6) || between(8, 9) => Split,
+                9 | 10 => Stand,
+                // Not split, so played as the total once that is settled
+                _ => Hit,
+            };
+            chart.set_pair(card, up, action);
+        }
+    }
+
+    // Surrender whatever is worst against a ten or an ace
+    match rules.surrender {
+        SurrenderRule::NotAllowed => {}
+        SurrenderRule::Late => {
+            chart.set_hard(16, 9, SurrenderOrHit);
+            chart.set_hard(16, 10, SurrenderOrHit);
+            chart.set_hard(16, 11, SurrenderOrHit);
+            chart.set_hard(15, 10, SurrenderOrHit);
+            if h17 {
+                chart.set_hard(15, 11, SurrenderOrHit);
+                chart.set_hard(17, 11, SurrenderOrStand);
+                chart.set_pair(8, 11, SurrenderOrSplit);
+            }
+        }
+        SurrenderRule::Early => {
+            for total in (5..=7).chain(12..=16) {
+                chart.set_hard(total, 11, SurrenderOrHit);
+            }
+            chart.set_hard(17, 11, SurrenderOrStand);
+            for total in 14..=16 {
+                chart.set_hard(total, 10, SurrenderOrHit);
+            }
+            for card in [3, 6, 7, 8] {
+                chart.set_pair(card, 11, SurrenderOrSplit);
+            }
+            for card in [7, 8] {
+                chart.set_pair(card, 10, SurrenderOrSplit);
+            }
+        }
+    }
+
+    // Without a hole card doubles and splits are lost to a dealer blackjack,
+    // so don't put more money out against a ten or an ace
+    if rules.no_hole_card {
+        for up in [10, 11] {
+            for total in 10..=11 {
+                chart.set_hard(total, up, Hit);
+            }
+            if chart.pair(8, up) == Split {
+                chart.set_pair(8, up, Hit);
+            }
+        }
+        chart.set_pair(11, 11, Hit);
+    }
+
+    // Pairs that aren't split are played as their total
+    for up in 2..=11 {
+        for card in Chart::PAIR_VALUES {
+            if !chart.pair(card, up).is_split() {
+                let action = if card == 11 {
+                    chart.soft(12, up)
+                } else {
+                    chart.hard(card * 2, up)
+                };
+                chart.set_pair(card, up, action);
+            }
+        }
+    }
+
+    chart
+}
+
+//@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@
+// Tests
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::{Rank, Suit};
+    use crate::deck::Deck;
+    use crate::game::Round;
+    use crate::money::Money;
+
+    fn cards(ranks: &[Rank]) -> Cards {
+        Cards(
+            ranks
+                .iter()
+                .map(|rank| Card::new(*rank, Suit::CLUBS))
+                .collect(),
+        )
+    }
+
+    fn upcard(rank: Rank) -> Card {
+        Card::new(rank, Suit::HEARTS)
+    }
+
+    const ALL: [Action; 5] = [
+        Action::Hit,
+        Action::Stand,
+        Action::Double,
+        Action::Split,
+        Action::Surrender,
+    ];
+    const HIT_STAND: [Action; 2] = [Action::Hit, Action::Stand];
+
+    #[test]
+    fn plays_the_usual_hands() {
+        let rules = RuleSet::default();
+        let strategy = BasicStrategy::new(&rules);
+        let decide = |hand: &[Rank], up, allowed: &[Action]| {
+            strategy.decide(&cards(hand), upcard(up), allowed, &rules)
+        };
+        assert_eq!(
+            decide(&[Rank::TEN, Rank::TWO], Rank::FOUR, &ALL),
+            Action::Stand
+        );
+        assert_eq!(
+            decide(&[Rank::TEN, Rank::SIX], Rank::TEN, &ALL),
+            Action::Hit
+        );
+        assert_eq!(
+            decide(&[Rank::SIX, Rank::FIVE], Rank::SIX, &ALL),
+            Action::Double
+        );
+        assert_eq!(
+            decide(&[Rank::ACE, Rank::ACE], Rank::TEN, &ALL),
+            Action::Split
+        );
+        assert_eq!(
+            decide(&[Rank::TEN, Rank::KING], Rank::SIX, &ALL),
+            Action::Stand
+        );
+        // Soft 18 doubles if it can, otherwise stands
+        assert_eq!(
+            decide(&[Rank::ACE, Rank::SEVEN], Rank::FOUR, &ALL),
+            Action::Double
+        );
+        assert_eq!(
+            decide(
+                &[Rank::ACE, Rank::FOUR, Rank::THREE],
+                Rank::FOUR,
+                &HIT_STAND
+            ),
+            Action::Stand
+        );
+    }
+
+    #[test]
+    fn follows_the_rules() {
+        let rules = RuleSet {
+            surrender: SurrenderRule::Late,
+            dealer: DealerRule::HitSoft17,
+            ..RuleSet::default()
+        };
+        let strategy = BasicStrategy::new(&rules);
+        let sixteen = cards(&[Rank::TEN, Rank::SIX]);
+        let eleven = cards(&[Rank::SIX, Rank::FIVE]);
+        assert_eq!(
+            strategy.decide(&sixteen, upcard(Rank::KING), &ALL, &rules),
+            Action::Surrender
+        );
+        assert_eq!(
+            strategy.decide(&eleven, upcard(Rank::ACE), &ALL, &rules),
+            Action::Double
+        );
+        assert_eq!(
+            BasicStrategy::new(&RuleSet::default()).decide(
+                &eleven,
+                upcard(Rank::ACE),
+                &ALL,
+                &RuleSet::default()
+            ),
+            Action::Hit
+        );
+    }
+
+    #[test]
+    fn plays_a_whole_round() {
+        // Player 6+5 against a 6 doubles, gets a ten and the dealer busts
+        let mut deck = Deck::from_cards(cards(&[
+            Rank::TEN,
+            Rank::TEN,
+            Rank::TEN,
+            Rank::SIX,
+            Rank::FIVE,
+            Rank::SIX,
+            Rank::SIX,
+        ]));
+        let rules = RuleSet::default();
+        let mut round = Round::deal(&mut deck, &rules, Money::dollars(10));
+        round.play(&BasicStrategy::new(&rules), &mut deck);
+        assert!(round.player().hand(0).is_doubled());
+        assert_eq!(round.net(), Money::dollars(20));
+    }
+}